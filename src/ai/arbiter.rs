@@ -0,0 +1,208 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use ai::strategies::{Candidate, StrategyType};
+use common::Direction;
+
+// A candidate direction together with the strategy that proposed it
+#[derive(Clone, Debug, Serialize)]
+pub struct Proposal {
+    pub strategy: StrategyType,
+    pub candidate: Candidate,
+}
+
+// Everything that went in to a decision, so that it's possible to tell afterwards why a direction was picked
+#[derive(Clone, Debug)]
+pub struct Breakdown {
+    pub tick: u32,
+    pub policy: &'static str,
+    pub proposals: Vec<Proposal>,
+    // Index in to the proposals
+    pub winner: Option<usize>,
+    pub explanation: String,
+}
+
+impl Breakdown {
+    pub fn winning_proposal(&self) -> Option<&Proposal> {
+        self.winner.map(|i| &self.proposals[i])
+    }
+}
+
+impl fmt::Display for Breakdown {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Tick {} ({} policy): {}", self.tick, self.policy, self.explanation)?;
+        for (i, proposal) in self.proposals.iter().enumerate() {
+            writeln!(f, "  {} {:?} {} {:.2} - {}",
+                if Some(i) == self.winner { "*" } else { " " },
+                proposal.strategy,
+                proposal.candidate.direction,
+                proposal.candidate.score,
+                proposal.candidate.reason)?;
+        }
+        Ok(())
+    }
+}
+
+// Every policy that can be picked from the configuration
+pub const POLICIES: [&str; 2] = ["priority", "weighted"];
+
+// Combines the proposals of all strategies in to a single decision
+pub trait Policy {
+    fn name(&self) -> &'static str;
+
+    // Returns the index of the winning proposal, and an explanation of why it won
+    fn choose(&self, proposals: &[Proposal]) -> (Option<usize>, String);
+}
+
+// The strategy that comes first wins, and its best scoring candidate is used
+pub struct PriorityPolicy {
+    order: Vec<StrategyType>,
+}
+
+impl PriorityPolicy {
+    pub fn new(order: Vec<StrategyType>) -> PriorityPolicy {
+        PriorityPolicy {
+            order,
+        }
+    }
+
+    fn rank(&self, strategy: &StrategyType) -> usize {
+        self.order
+            .iter()
+            .position(|s| s == strategy)
+            .unwrap_or(self.order.len())
+    }
+}
+
+impl Policy for PriorityPolicy {
+    fn name(&self) -> &'static str {
+        "priority"
+    }
+
+    fn choose(&self, proposals: &[Proposal]) -> (Option<usize>, String) {
+        // Ties keep the first proposal, so the order strategies are asked in is kept within a rank
+        let winner = proposals
+            .iter()
+            .enumerate()
+            .fold(None, |best: Option<(usize, &Proposal)>, (i, p)| match best {
+                Some((_, b)) if (self.rank(&b.strategy), -b.candidate.score) <= (self.rank(&p.strategy), -p.candidate.score) => best,
+                _ => Some((i, p)),
+            });
+
+        match winner {
+            Some((i, p)) => (Some(i), format!("{:?} has the highest priority of the {} proposals", p.strategy, proposals.len())),
+            None => (None, "No strategy proposed anything".to_string()),
+        }
+    }
+}
+
+// Sums the weighted scores for each direction, the direction with the highest total wins
+pub struct WeightedPolicy {
+    weights: HashMap<StrategyType, f32>,
+}
+
+impl WeightedPolicy {
+    pub fn new(weights: HashMap<StrategyType, f32>) -> WeightedPolicy {
+        WeightedPolicy {
+            weights,
+        }
+    }
+
+    fn weight(&self, strategy: &StrategyType) -> f32 {
+        self.weights.get(strategy).cloned().unwrap_or(1.0)
+    }
+}
+
+impl Policy for WeightedPolicy {
+    fn name(&self) -> &'static str {
+        "weighted"
+    }
+
+    fn choose(&self, proposals: &[Proposal]) -> (Option<usize>, String) {
+        let mut totals: Vec<(Direction, f32)> = Vec::new();
+        for p in proposals {
+            let score = p.candidate.score * self.weight(&p.strategy);
+            match totals.iter().position(|(d, _)| *d == p.candidate.direction) {
+                Some(i) => totals[i].1 += score,
+                None => totals.push((p.candidate.direction.clone(), score)),
+            }
+        }
+
+        let best = totals
+            .iter()
+            .fold(None, |best: Option<&(Direction, f32)>, t| match best {
+                Some(b) if b.1 >= t.1 => best,
+                _ => Some(t),
+            });
+
+        let (direction, total) = match best {
+            Some(&(ref d, t)) => (d.clone(), t),
+            None => return (None, "No strategy proposed anything".to_string()),
+        };
+
+        // Credit the proposal that contributed the most to the winning direction
+        let winner = proposals
+            .iter()
+            .enumerate()
+            .filter(|&(_, p)| p.candidate.direction == direction)
+            .fold(None, |best: Option<(usize, f32)>, (i, p)| {
+                let score = p.candidate.score * self.weight(&p.strategy);
+                match best {
+                    Some((_, s)) if s >= score => best,
+                    _ => Some((i, score)),
+                }
+            })
+            .map(|(i, _)| i);
+
+        let others = totals
+            .iter()
+            .filter(|&(d, _)| *d != direction)
+            .map(|&(ref d, t)| format!("{} {:.2}", d, t))
+            .collect::<Vec<String>>();
+
+        (winner, format!("{} scored {:.2} in total, against [{}]", direction, total, others.join(", ")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn proposal(strategy: StrategyType, direction: Direction, score: f32) -> Proposal {
+        Proposal {
+            strategy,
+            candidate: Candidate::new(direction, score, String::new()),
+        }
+    }
+
+    #[test]
+    fn priority_policy_should_pick_highest_priority_strategy() {
+        let policy = PriorityPolicy::new(vec![StrategyType::Avoidance, StrategyType::PickPellets]);
+        let proposals = vec![
+            proposal(StrategyType::PickPellets, Direction::Left, 1.0),
+            proposal(StrategyType::Avoidance, Direction::Up, 0.2),
+            proposal(StrategyType::Avoidance, Direction::Down, 0.8),
+        ];
+
+        assert_eq!(Some(2), policy.choose(&proposals).0);
+        assert_eq!(None, policy.choose(&[]).0);
+    }
+
+    #[test]
+    fn weighted_policy_should_sum_scores_per_direction() {
+        let mut weights = HashMap::new();
+        weights.insert(StrategyType::Avoidance, 2.0);
+        let policy = WeightedPolicy::new(weights);
+
+        let proposals = vec![
+            proposal(StrategyType::Avoidance, Direction::Up, 0.5),
+            proposal(StrategyType::PickPellets, Direction::Left, 0.8),
+            proposal(StrategyType::Hunter, Direction::Left, 0.3),
+        ];
+
+        // Left gets 1.1 and Up gets 1.0, but the pellets contributed the most to Left
+        let (winner, explanation) = policy.choose(&proposals);
+        assert_eq!(Some(1), winner);
+        assert!(explanation.starts_with("LEFT scored 1.10"));
+    }
+}
@@ -0,0 +1,329 @@
+use serde_json;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use ai::arbiter;
+use ai::strategies;
+use common::rules;
+
+// Tunable values for a strategy, anything not given falls back to the default of the strategy
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct Params(HashMap<String, Value>);
+
+impl Params {
+    pub fn set(&mut self, name: &str, value: Value) {
+        self.0.insert(name.to_string(), value);
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.0.keys().map(|k| k.as_str()).collect();
+        names.sort();
+        names
+    }
+
+    pub fn usize_or(&self, name: &str, default: usize) -> usize {
+        self.0.get(name).and_then(|v| v.as_f64()).map(|v| v as usize).unwrap_or(default)
+    }
+
+    pub fn f32_or(&self, name: &str, default: f32) -> f32 {
+        self.0.get(name).and_then(|v| v.as_f64()).map(|v| v as f32).unwrap_or(default)
+    }
+
+    pub fn str_or<'a>(&'a self, name: &str, default: &'a str) -> &'a str {
+        self.0.get(name).and_then(|v| v.as_str()).unwrap_or(default)
+    }
+
+    // Whether a param is left out or holds a whole number that isn't negative, like a number of ticks
+    pub fn is_count(&self, name: &str) -> bool {
+        self.0.get(name).map(|v| v.as_f64().map(|n| n >= 0.0 && n.fract() == 0.0).unwrap_or(false)).unwrap_or(true)
+    }
+
+    pub fn is_number(&self, name: &str) -> bool {
+        self.0.get(name).map(|v| v.is_number()).unwrap_or(true)
+    }
+
+    pub fn is_text(&self, name: &str) -> bool {
+        self.0.get(name).map(|v| v.is_string()).unwrap_or(true)
+    }
+
+    // What the server awards points for, falling back to our estimates
+    pub fn scores(&self) -> rules::Scores {
+        let estimated = rules::ESTIMATED_SCORES;
+        rules::Scores {
+            pellet: self.usize_or("pellet_score", estimated.pellet as usize) as u32,
+            super_pellet: self.usize_or("super_pellet_score", estimated.super_pellet as usize) as u32,
+            eat_player: self.usize_or("eat_player_score", estimated.eat_player as usize) as u32,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct StrategyConfig {
+    pub name: String,
+    #[serde(default = "enabled_by_default")]
+    pub enabled: bool,
+    #[serde(default)]
+    pub params: Params,
+    // How much the proposals of the strategy count for under the weighted policy
+    #[serde(default = "default_weight")]
+    pub weight: f32,
+}
+
+impl StrategyConfig {
+    fn check_params(&self) -> Result<(), Error> {
+        let known = strategies::param_names(&self.name);
+        if let Some(param) = self.params.names().into_iter().find(|p| !known.contains(p)) {
+            return Err(Error::UnknownParameter(format!("{}.{}", self.name, param)));
+        }
+
+        strategies::check_params(&self.name, &self.params)
+            .map_err(|param| Error::InvalidParameter(format!("{}.{}", self.name, param)))
+    }
+}
+
+fn enabled_by_default() -> bool {
+    true
+}
+
+fn default_weight() -> f32 {
+    1.0
+}
+
+fn priority_policy() -> String {
+    "priority".to_string()
+}
+
+// Which strategies to use and how to tune them, the order of the strategies is their priority
+#[derive(Clone, Debug, Deserialize)]
+pub struct BotConfig {
+    // How the proposals of the strategies are combined, `priority` or `weighted`
+    #[serde(default = "priority_policy")]
+    pub policy: String,
+    pub strategies: Vec<StrategyConfig>,
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    Deserialization(serde_json::error::Error),
+    UnknownStrategy(String),
+    UnknownPolicy(String),
+    // The strategy exists, but it's not in the config
+    NotConfigured(String),
+    DuplicateStrategy(String),
+    UnknownParameter(String),
+    InvalidParameter(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Io(ref e) => write!(f, "can't read the file, {}", e),
+            Error::Deserialization(ref e) => write!(f, "not a valid config, {}", e),
+            Error::UnknownStrategy(ref name) => write!(f, "there is no strategy called {}", name),
+            Error::UnknownPolicy(ref name) => write!(f, "there is no policy called {}", name),
+            Error::NotConfigured(ref name) => write!(f, "{} is not in the config", name),
+            Error::DuplicateStrategy(ref name) => write!(f, "{} is listed more than once", name),
+            Error::UnknownParameter(ref name) => write!(f, "unknown parameter {}", name),
+            Error::InvalidParameter(ref name) => write!(f, "invalid parameter {}", name),
+        }
+    }
+}
+
+impl Default for BotConfig {
+    fn default() -> BotConfig {
+        let strategy = |name: &str, enabled: bool| StrategyConfig {
+            name: name.to_string(),
+            enabled,
+            params: Params::default(),
+            weight: default_weight(),
+        };
+
+        BotConfig {
+            policy: priority_policy(),
+            strategies: vec![
+                strategy("spawn_escape", true),
+                strategy("avoidance", true),
+                strategy("expectimax", false),
+                strategy("cornering", true),
+                strategy("hunter", true),
+                strategy("killer", false),
+                strategy("mcts", false),
+                strategy("endgame", true),
+                strategy("pick_pellets", true),
+            ],
+        }
+    }
+}
+
+impl BotConfig {
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<BotConfig, Error> {
+        let json = fs::read_to_string(path).map_err(Error::Io)?;
+        BotConfig::from_json(&json)
+    }
+
+    pub fn from_json(json: &str) -> Result<BotConfig, Error> {
+        let config: BotConfig = serde_json::from_str(json).map_err(Error::Deserialization)?;
+        config.validate()
+    }
+
+    fn validate(self) -> Result<BotConfig, Error> {
+        if !arbiter::POLICIES.contains(&self.policy.as_str()) {
+            return Err(Error::UnknownPolicy(self.policy.clone()));
+        }
+
+        for (i, s) in self.strategies.iter().enumerate() {
+            if !strategies::is_registered(&s.name) {
+                return Err(Error::UnknownStrategy(s.name.clone()));
+            }
+            if self.strategies[..i].iter().any(|other| other.name == s.name) {
+                return Err(Error::DuplicateStrategy(s.name.clone()));
+            }
+            s.check_params()?;
+        }
+
+        Ok(self)
+    }
+
+    pub fn enabled_strategies(&self) -> Vec<&StrategyConfig> {
+        self.strategies
+            .iter()
+            .filter(|s| s.enabled)
+            .collect()
+    }
+
+    // Enabling a strategy that isn't in the config adds it with the lowest priority
+    pub fn set_enabled(&mut self, name: &str, enabled: bool) -> Result<(), Error> {
+        if !strategies::is_registered(name) {
+            return Err(Error::UnknownStrategy(name.to_string()));
+        }
+
+        match self.strategies.iter_mut().find(|s| s.name == name) {
+            Some(s) => s.enabled = enabled,
+            None => self.strategies.push(StrategyConfig {
+                name: name.to_string(),
+                enabled,
+                params: Params::default(),
+                weight: default_weight(),
+            }),
+        }
+
+        Ok(())
+    }
+
+    // Moves the given strategies to the top, in the given order
+    pub fn prioritize(&mut self, names: &[&str]) -> Result<(), Error> {
+        for name in names.iter().rev() {
+            let index = match self.strategies.iter().position(|s| s.name == *name) {
+                Some(i) => i,
+                None if strategies::is_registered(name) => return Err(Error::NotConfigured(name.to_string())),
+                None => return Err(Error::UnknownStrategy(name.to_string())),
+            };
+
+            let strategy = self.strategies.remove(index);
+            self.strategies.insert(0, strategy);
+        }
+
+        Ok(())
+    }
+
+    // Sets a parameter from a string like "avoidance.radius=3", anything that isn't a number is kept as text
+    pub fn set_param(&mut self, assignment: &str) -> Result<(), Error> {
+        let invalid = || Error::InvalidParameter(assignment.to_string());
+
+        let mut parts = assignment.splitn(2, '=');
+        let key = parts.next().unwrap_or("");
+        let value = match parts.next().map(|v| v.trim()) {
+            Some(v) if !v.is_empty() => v.parse::<f64>().map(Value::from).unwrap_or_else(|_| Value::String(v.to_string())),
+            _ => return Err(invalid()),
+        };
+
+        let mut key_parts = key.trim().splitn(2, '.');
+        let (name, param) = match (key_parts.next(), key_parts.next()) {
+            (Some(n), Some(p)) if !p.is_empty() => (n, p),
+            _ => return Err(invalid()),
+        };
+
+        match self.strategies.iter_mut().find(|s| s.name == name) {
+            // Nothing changes unless the strategy can make sense of it
+            Some(s) => {
+                let previous = s.params.clone();
+                s.params.set(param, value);
+                let checked = s.check_params();
+                if checked.is_err() {
+                    s.params = previous;
+                }
+                checked
+            },
+            None if strategies::is_registered(name) => Err(Error::NotConfigured(name.to_string())),
+            None => Err(Error::UnknownStrategy(name.to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_load_config_from_json() {
+        const CONFIG: &str = r#"{"strategies":[{"name":"killer"},{"name":"avoidance","params":{"radius":2}},{"name":"hunter","enabled":false}]}"#;
+        let config = BotConfig::from_json(CONFIG).unwrap();
+
+        let enabled = config.enabled_strategies();
+        assert_eq!(2, enabled.len());
+        assert_eq!("killer", enabled[0].name);
+        assert_eq!(2, enabled[1].params.usize_or("radius", 3));
+        assert_eq!(4, enabled[1].params.usize_or("dead_end_margin", 4));
+
+        assert!(BotConfig::from_json(r#"{"strategies":[{"name":"camper"}]}"#).is_err());
+        assert!(BotConfig::from_json(r#"{"policy":"random","strategies":[]}"#).is_err());
+        assert!(BotConfig::from_json(r#"{"strategies":[{"name":"avoidance","params":{"radus":2}}]}"#).is_err());
+        let duplicate = BotConfig::from_json(r#"{"strategies":[{"name":"killer"},{"name":"hunter"},{"name":"killer"}]}"#);
+        assert!(matches!(duplicate, Err(Error::DuplicateStrategy(ref name)) if name == "killer"));
+
+        let weighted = BotConfig::from_json(r#"{"policy":"weighted","strategies":[{"name":"killer","weight":2}]}"#).unwrap();
+        assert_eq!("weighted", weighted.policy);
+        assert_eq!(2.0, weighted.strategies[0].weight);
+        assert!(BotConfig::from_json(r#"{"strategies":[{"name":"mcts","params":{"own_policy":"gredy"}}]}"#).is_err());
+        let mistyped = BotConfig::from_json(r#"{"strategies":[{"name":"avoidance","params":{"radius":"3"}}]}"#);
+        assert!(matches!(mistyped, Err(Error::InvalidParameter(ref param)) if param == "avoidance.radius"));
+    }
+
+    #[test]
+    fn can_override_config() {
+        let mut config = BotConfig::default();
+        config.set_enabled("killer", true).unwrap();
+        config.prioritize(&["pick_pellets", "killer"]).unwrap();
+        config.set_param("avoidance.warmup_ticks=0").unwrap();
+        config.set_param("mcts.own_policy=random").unwrap();
+
+        let names: Vec<&str> = config.enabled_strategies().iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(vec!["pick_pellets", "killer", "spawn_escape", "avoidance", "cornering", "hunter", "endgame"], names);
+        let params = |name: &str| &config.strategies.iter().find(|s| s.name == name).unwrap().params;
+        assert_eq!(0, params("avoidance").usize_or("warmup_ticks", 5));
+        assert_eq!("random", params("mcts").str_or("own_policy", "greedy"));
+
+        assert!(config.set_param("avoidance.radius").is_err());
+        assert!(config.set_param("mcts.opponent_policy=chasr").is_err());
+        assert!(config.set_param("mcts.rollout_depth=0").is_err());
+        assert!(matches!(config.set_param("hunter.mode=lazy"), Err(Error::UnknownParameter(_))));
+        assert!(matches!(config.set_param("camper.radius=3"), Err(Error::UnknownStrategy(_))));
+        for assignment in &["avoidance.radius=abc", "avoidance.radius=-2", "avoidance.radius=2.7", "cornering.margin=-1", "endgame.pellets_left=2.5", "endgame.pellet_score=many", "expectimax.mode=3", "expectimax.mode=random", "expectimax.horizon=-10", "mcts.exploration=high", "pick_pellets.route_horizon=abc"] {
+            assert!(matches!(config.set_param(assignment), Err(Error::InvalidParameter(_))), "{}", assignment);
+        }
+        config.set_param("mcts.exploration=0.5").unwrap();
+        config.set_param("expectimax.mode=expectimax").unwrap();
+
+        let mut config = BotConfig::from_json(r#"{"strategies":[{"name":"killer"}]}"#).unwrap();
+        assert!(matches!(config.set_param("avoidance.radius=3"), Err(Error::NotConfigured(_))));
+        assert!(config.set_param("radius=3").is_err());
+        assert!(config.set_enabled("camper", true).is_err());
+        assert!(matches!(config.prioritize(&["avoidance"]), Err(Error::NotConfigured(_))));
+        assert!(matches!(config.prioritize(&["camper"]), Err(Error::UnknownStrategy(_))));
+    }
+}
@@ -0,0 +1,116 @@
+use std::collections::{HashMap, HashSet};
+
+use ai::is_threat;
+use common::Position;
+use game::{DistanceTable, MapInformation};
+use protocol::Player;
+use traits::HasPosition;
+
+// How far away an enemy has to be before we stop caring about it
+const DANGER_RADIUS: usize = 6;
+
+// Enemies that can eat us are a lot worse to be close to than those we might just bump in to
+const DANGEROUS_ENEMY_WEIGHT: u32 = 4;
+const HARMLESS_ENEMY_WEIGHT: u32 = 1;
+
+const DEAD_END_WEIGHT: u32 = 2;
+
+pub trait DangerField {
+    // Extra cost of walking on a position, None if it should not be entered at all
+    fn danger_at(&self, position: &Position) -> Option<u32>;
+}
+
+// Danger based on how close enemies are, and how easy it is to get trapped in dead ends near them
+pub struct EnemyDangerField {
+    costs: HashMap<Position, u32>,
+    blocked: HashSet<Position>,
+}
+
+impl EnemyDangerField {
+    pub fn new() -> EnemyDangerField {
+        EnemyDangerField {
+            costs: HashMap::new(),
+            blocked: HashSet::new(),
+        }
+    }
+
+    pub fn update(&mut self, map_information: &MapInformation, distances: &DistanceTable, enemies: &[Player], can_eat_others: bool) {
+        self.costs.clear();
+        self.blocked.clear();
+
+        // Enemies we can eat are not a threat
+        let threats: Vec<(Position, u32)> = enemies
+            .iter()
+            .filter(|e| is_threat(e, can_eat_others))
+            .map(|e| (e.position(), if e.is_dangerous { DANGEROUS_ENEMY_WEIGHT } else { HARMLESS_ENEMY_WEIGHT }))
+            .collect();
+
+        if threats.is_empty() {
+            return;
+        }
+
+        for position in map_information.walkable_positions() {
+            let mut cost = 0;
+            for &(ref enemy, weight) in &threats {
+                let distance = distances.distance(enemy, position).unwrap_or(usize::MAX);
+                if distance == 0 {
+                    self.blocked.insert(position.clone());
+                }
+                else if distance < DANGER_RADIUS {
+                    cost += weight * ((DANGER_RADIUS - distance) * (DANGER_RADIUS - distance)) as u32;
+                }
+
+                // A dead end is only dangerous if someone can block the exit before we get out
+                if let Some(dead_end) = map_information.dead_end(position) {
+                    let distance_to_exit = distances.distance(enemy, &dead_end.exit).unwrap_or(usize::MAX);
+                    if distance_to_exit <= dead_end.depth * 2 + DANGER_RADIUS {
+                        cost += weight * DEAD_END_WEIGHT * dead_end.depth as u32;
+                    }
+                }
+            }
+
+            if cost > 0 {
+                self.costs.insert(position.clone(), cost);
+            }
+        }
+    }
+}
+
+impl DangerField for EnemyDangerField {
+    fn danger_at(&self, position: &Position) -> Option<u32> {
+        if self.blocked.contains(position) {
+            return None;
+        }
+
+        Some(self.costs.get(position).cloned().unwrap_or(0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json;
+    use game::Map;
+
+    const DEFAULT_MAP: &str = r#"{"content":["||||||||||||||||||||||||||||","|............||............|","|.||||.|||||.||.|||||.||||.|","|o||||.|||||.||.|||||.||||.|","|.||||.|||||.||.|||||.||||.|","|....|................|....|","|.||||.||.||||||||.||.||||.|","|.||||.||.||||||||.||.||||.|","|....|.||....||....||.|....|","||||||.|||||_||_|||||.||||||","_____|.|||||_||_|||||.|_____","_____|.||__________||.|_____","_____|.||_|||--|||_||.|_____","||||||.||_|______|_||.||||||","______.___|______|___.______","||||||.||_|______|_||.||||||","_____|.||_|||--|||_||.|_____","_____|.||__________||.|_____","_____|.||_||||||||_||.|_____","||||||.||_||||||||_||.||||||","|....|.......||.......|....|","|.||||.|||||.||.|||||.||||.|","|.||||.|||||.||.|||||.||||.|","|o..||.......__.......||..o|","|||.||.||.||||||||.||.||.|||","|||.||.||.||||||||.||.||.|||","|......||....||....||......|","|.||||||||||.||.||||||||||.|","|.||||||||||.||.||||||||||.|","|..........................|","||||||||||||||||||||||||||||"],"height":31,"pelletsleft":238,"width":28}"#;
+
+    #[test]
+    fn danger_should_depend_on_enemy_state() {
+        let map: Map = serde_json::from_str(DEFAULT_MAP).unwrap();
+        let info = MapInformation::from_map(&map);
+        let distances = DistanceTable::from_map(&map);
+        let enemies: Vec<Player> = serde_json::from_str(r#"[{"id":1,"x":12,"y":5,"isdangerous":true}]"#).unwrap();
+
+        let mut field = EnemyDangerField::new();
+        field.update(&info, &distances, &enemies, false);
+
+        assert_eq!(None, field.danger_at(&Position::new(12, 5)));
+        assert!(field.danger_at(&Position::new(13, 5)).unwrap() > field.danger_at(&Position::new(15, 5)).unwrap());
+        assert_eq!(Some(0), field.danger_at(&Position::new(26, 29)));
+
+        // Can't be eaten by someone we can eat
+        let enemies: Vec<Player> = serde_json::from_str(r#"[{"id":1,"x":12,"y":5,"isdangerous":false}]"#).unwrap();
+        field.update(&info, &distances, &enemies, true);
+        assert_eq!(Some(0), field.danger_at(&Position::new(12, 5)));
+    }
+}
@@ -0,0 +1,114 @@
+use std::fmt;
+
+use ai::strategies::StrategyType;
+use common::{Direction, Position};
+use game::DistanceTable;
+use protocol::GameState;
+use traits::HasPosition;
+
+// What we know about how we died, pieced together from the last state we saw before it happened
+#[derive(Clone, Debug, PartialEq)]
+pub struct Death {
+    pub tick: u32,
+    // Where we were headed when it happened
+    pub position: Position,
+    // The closest enemy that could have eaten us, if there was one
+    pub killer: Option<u32>,
+    pub strategy: Option<StrategyType>,
+}
+
+impl Death {
+    pub fn from_last_state(tick: u32, last: &GameState, direction: &Direction, distances: &DistanceTable, strategy: Option<StrategyType>, was_dangerous: bool) -> Death {
+        let position = last.me.position().adjacent(&last.map, direction);
+
+        // We both moved a step, so whoever got us was at most two steps away
+        let killer = last.enemies
+            .iter()
+            .filter(|e| e.is_dangerous && !was_dangerous)
+            .filter_map(|e| distances.distance(&e.position(), &position).map(|d| (d, e.id)))
+            .filter(|&(d, _)| d <= 2)
+            .min()
+            .map(|(_, id)| id);
+
+        Death {
+            tick,
+            position,
+            killer,
+            strategy,
+        }
+    }
+}
+
+// How many times each strategy was in control when we died, the one that got us killed the most first
+pub fn blame(deaths: &[Death]) -> Vec<(Option<StrategyType>, usize)> {
+    let mut counts: Vec<(Option<StrategyType>, usize)> = Vec::new();
+    for death in deaths {
+        match counts.iter().position(|&(s, _)| s == death.strategy) {
+            Some(i) => counts[i].1 += 1,
+            None => counts.push((death.strategy, 1)),
+        }
+    }
+
+    counts.sort_by_key(|&(_, count)| ::std::cmp::Reverse(count));
+    counts
+}
+
+impl fmt::Display for Death {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Died at tick {} around {}", self.tick, self.position)?;
+        match self.killer {
+            Some(id) => write!(f, ", eaten by {}", id)?,
+            None => write!(f, ", no idea who did it")?,
+        }
+        match self.strategy {
+            Some(ref strategy) => write!(f, " while {:?} was in control", strategy),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ai::Bot;
+    use protocol::Message;
+    use std::str::FromStr;
+
+    fn parse(me: (u32, u32)) -> GameState {
+        let message = format!(r#"{{"messagetype":"stateupdate","gamestate":{{"map":{{"content":["|||||||||||","|______...|","|||||||||||"],"height":3,"width":11,"pelletsleft":3}},"you":{{"id":0,"x":{},"y":{}}},"others":[{{"id":5,"x":6,"y":1,"isdangerous":true}},{{"id":6,"x":9,"y":1}}]}}}}"#, me.0, me.1);
+        match Message::from_str(&message).unwrap() {
+            Message::Update { state } => state,
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn should_record_death_and_start_a_new_life() {
+        let mut bot = Bot::from_game_state(&parse((5, 1)));
+        bot.determine_action(parse((5, 1)));
+        bot.on_death();
+
+        let death = bot.deaths().last().unwrap().clone();
+        assert_eq!(Some(5), death.killer);
+        assert_eq!(1, death.tick);
+        assert!(bot.previous_state.is_none());
+        assert!(bot.previous_strategy_type.is_none());
+
+        // Showing up somewhere else without being told we died counts as well
+        bot.determine_action(parse((4, 1)));
+        bot.determine_action(parse((1, 1)));
+        assert_eq!(2, bot.deaths().len());
+        assert_eq!(2, blame(bot.deaths()).iter().map(|&(_, count)| count).sum::<usize>());
+
+        // Deaths are counted per round
+        bot.reset();
+        assert!(bot.deaths().is_empty());
+    }
+
+    #[test]
+    fn should_blame_the_strategy_in_control_most_often() {
+        let death = |strategy| Death { tick: 1, position: Position::new(1, 1), killer: None, strategy };
+        let deaths = vec![death(Some(StrategyType::Hunter)), death(None), death(Some(StrategyType::PickPellets)), death(Some(StrategyType::PickPellets))];
+        assert_eq!(vec![(Some(StrategyType::PickPellets), 2), (Some(StrategyType::Hunter), 1), (None, 1)], blame(&deaths));
+    }
+}
@@ -0,0 +1,168 @@
+use std::collections::VecDeque;
+
+use common::Position;
+use game::Map;
+use traits::HasDimensions;
+
+// Which source got to a tile first, and in how many steps
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Reach {
+    pub source: usize,
+    pub distance: usize,
+}
+
+// Distance from every walkable tile to the closest of a set of sources, like all enemies or all pellets.
+// The two closest distinct sources are kept for each tile, so it's also known who gets there and by how much.
+pub struct DistanceField {
+    size: (u32, u32),
+    sources: Vec<Position>,
+
+    closest: Vec<Option<Reach>>,
+    runner_up: Vec<Option<Reach>>,
+}
+
+impl HasDimensions for DistanceField {
+    fn width(&self) -> u32 {
+        self.size.0
+    }
+    fn height(&self) -> u32 {
+        self.size.1
+    }
+}
+
+impl DistanceField {
+    pub fn new(map: &Map) -> DistanceField {
+        DistanceField {
+            size: (map.width(), map.height()),
+            sources: Vec::new(),
+            closest: vec![None; map.tiles().len()],
+            runner_up: vec![None; map.tiles().len()],
+        }
+    }
+
+    pub fn update_from_map<I>(&mut self, map: &Map, sources: I)
+        where I: IntoIterator<Item = Position> {
+        // Clear any existing information
+        self.sources.clear();
+        self.sources.extend(sources);
+        for tile in self.closest.iter_mut().chain(self.runner_up.iter_mut()) {
+            *tile = None;
+        }
+
+        // Multi-source BFS where every tile accepts the first two distinct sources that reach it
+        let mut frontier = VecDeque::new();
+        for source in 0..self.sources.len() {
+            let position = self.sources[source].clone();
+            if self.visit(&position, source, 0) {
+                frontier.push_back((position, source, 0));
+            }
+        }
+
+        while let Some((current, source, distance)) = frontier.pop_front() {
+            for adjacent in current.neighbours(map) {
+                if !map.tile_at(&adjacent).is_walkable() {
+                    continue;
+                }
+
+                if self.visit(&adjacent, source, distance + 1) {
+                    frontier.push_back((adjacent, source, distance + 1));
+                }
+            }
+        }
+    }
+
+    // Returns true if this was the first or second distinct source to reach the position
+    fn visit(&mut self, position: &Position, source: usize, distance: usize) -> bool {
+        let index = match self.index_of(position) {
+            Some(i) => i,
+            None => return false,
+        };
+
+        let reach = Some(Reach { source, distance });
+        match self.closest[index] {
+            None => self.closest[index] = reach,
+            Some(ref closest) if closest.source == source => return false,
+            Some(_) if self.runner_up[index].is_some() => return false,
+            Some(_) => self.runner_up[index] = reach,
+        }
+
+        true
+    }
+
+    fn index_of(&self, position: &Position) -> Option<usize> {
+        if position.x >= self.size.0 || position.y >= self.size.1 {
+            return None;
+        }
+
+        Some((position.y * self.size.0 + position.x) as usize)
+    }
+
+    pub fn source(&self, index: usize) -> &Position {
+        &self.sources[index]
+    }
+
+    // Steps from the position to the closest source, None if no source can reach it
+    pub fn distance_at(&self, position: &Position) -> Option<usize> {
+        self.closest_at(position).map(|r| r.distance)
+    }
+
+    pub fn closest_at(&self, position: &Position) -> Option<Reach> {
+        self.index_of(position).and_then(|i| self.closest[i])
+    }
+
+    // The closest source other than the closest one
+    pub fn runner_up_at(&self, position: &Position) -> Option<Reach> {
+        self.index_of(position).and_then(|i| self.runner_up[i])
+    }
+
+    pub fn closest_source_to(&self, position: &Position) -> Option<&Position> {
+        self.closest_at(position).map(|r| self.source(r.source))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json;
+
+    const CROSS: &'static str = r#"
+{
+    "content": [
+        "|||||||||",
+        "|_______|",
+        "||||_||||",
+        "||||_||||",
+        "|||||||||"
+    ],
+    "height": 5,
+    "pelletsleft": 0,
+    "width": 9
+}"#;
+
+    #[test]
+    fn can_find_closest_sources() {
+        let map: Map = serde_json::from_str(CROSS).unwrap();
+        let mut field = DistanceField::new(&map);
+        field.update_from_map(&map, vec![Position::new(1, 1), Position::new(7, 1)]);
+
+        assert_eq!(Some(0), field.distance_at(&Position::new(1, 1)));
+        assert_eq!(Some(Reach { source: 0, distance: 2 }), field.closest_at(&Position::new(3, 1)));
+        assert_eq!(Some(Reach { source: 1, distance: 4 }), field.runner_up_at(&Position::new(3, 1)));
+        assert_eq!(Some(&Position::new(7, 1)), field.closest_source_to(&Position::new(6, 1)));
+        assert_eq!(Some(5), field.distance_at(&Position::new(4, 3)));
+
+        // Walls are never reached
+        assert_eq!(None, field.distance_at(&Position::new(0, 0)));
+    }
+
+    #[test]
+    fn is_empty_without_sources() {
+        let map: Map = serde_json::from_str(CROSS).unwrap();
+        let mut field = DistanceField::new(&map);
+        field.update_from_map(&map, vec![Position::new(4, 3)]);
+        field.update_from_map(&map, vec![]);
+
+        assert_eq!(None, field.distance_at(&Position::new(4, 3)));
+        assert_eq!(None, field.closest_source_to(&Position::new(4, 1)));
+    }
+}
@@ -0,0 +1,155 @@
+use std::f32;
+
+use ai::opponents::OpponentTracker;
+use common::Position;
+use game::{DistanceTable, Map};
+use protocol::Player;
+use traits::HasPosition;
+
+// Only the closest few pellets are considered to be what an enemy is going for
+const MAX_TARGETS: usize = 6;
+
+// How much less likely a pellet is for every step it's further away than the closest one
+const DISTANCE_FALLOFF: f32 = 0.5;
+
+// Enemies tend to keep going the way they were going
+const HEADING_BONUS: f32 = 3.0;
+const TURNING_AROUND_PENALTY: f32 = 0.2;
+
+// Pellets this close together are treated as one region to go for
+const REGION_RADIUS: usize = 3;
+
+pub struct EnemyIntent {
+    pub id: u32,
+    // Likely target pellets with their probabilities, most likely first
+    pub targets: Vec<(Position, f32)>,
+}
+
+// Guesses which pellet each enemy is going for, from where it's heading and which pellets are close to it
+pub struct IntentPredictor {
+    intents: Vec<EnemyIntent>,
+}
+
+impl IntentPredictor {
+    pub fn new() -> IntentPredictor {
+        IntentPredictor {
+            intents: Vec::new(),
+        }
+    }
+
+    pub fn update(&mut self, map: &Map, distances: &DistanceTable, enemies: &[Player], opponents: &OpponentTracker) {
+        let pellets = map.pellets();
+        self.intents.clear();
+
+        for enemy in enemies {
+            let position = enemy.position();
+            let mut reachable: Vec<(usize, &Position)> = pellets
+                .iter()
+                .filter_map(|p| distances.distance(&position, p).map(|d| (d, p)))
+                .collect();
+            reachable.sort_by_key(|&(d, p)| (d, p.y, p.x));
+            reachable.truncate(MAX_TARGETS);
+
+            let closest = match reachable.first() {
+                Some(&(d, _)) => d,
+                None => {
+                    self.intents.push(EnemyIntent { id: enemy.id, targets: Vec::new() });
+                    continue;
+                },
+            };
+
+            let last_direction = opponents.last_direction(enemy.id);
+            let mut targets: Vec<(Position, f32)> = reachable
+                .into_iter()
+                .map(|(d, p)| {
+                    let heading = match (distances.next_hop(&position, p), last_direction) {
+                        (Some(ref first), Some(last)) if first == last => HEADING_BONUS,
+                        (Some(ref first), Some(last)) if first.is_opposite_to(last) => TURNING_AROUND_PENALTY,
+                        _ => 1.0,
+                    };
+                    (p.clone(), heading * (-DISTANCE_FALLOFF * (d - closest) as f32).exp())
+                })
+                .collect();
+
+            let total: f32 = targets.iter().map(|&(_, w)| w).sum();
+            for target in &mut targets {
+                target.1 /= total;
+            }
+            targets.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap());
+
+            self.intents.push(EnemyIntent { id: enemy.id, targets });
+        }
+    }
+
+    pub fn targets(&self, id: u32) -> &[(Position, f32)] {
+        self.intents
+            .iter()
+            .find(|i| i.id == id)
+            .map(|i| &i.targets[..])
+            .unwrap_or(&[])
+    }
+
+    // Probability that the enemy is going for a pellet in the region around the position
+    pub fn claim(&self, id: u32, distances: &DistanceTable, position: &Position) -> f32 {
+        self.targets(id)
+            .iter()
+            .filter(|&(t, _)| distances.distance(t, position).map(|d| d <= REGION_RADIUS).unwrap_or(false))
+            .map(|&(_, p)| p)
+            .sum::<f32>()
+            .min(1.0)
+    }
+
+    // The strongest claim any enemy has on the region around the position
+    pub fn strongest_claim(&self, distances: &DistanceTable, position: &Position) -> f32 {
+        self.intents
+            .iter()
+            .map(|i| self.claim(i.id, distances, position))
+            .fold(0.0, f32::max)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ai::distance_field::DistanceField;
+    use ai::opponents::Context;
+    use serde_json;
+
+    const MAP: &str = r#"
+{
+    "content": [
+        "|||||||||||||",
+        "|..___.___..|",
+        "|||||||||||||"
+    ],
+    "height": 3,
+    "pelletsleft": 5,
+    "width": 13
+}"#;
+
+    #[test]
+    fn should_expect_enemy_to_keep_heading_for_pellets() {
+        let map: Map = serde_json::from_str(MAP).unwrap();
+        let distances = DistanceTable::from_map(&map);
+        let mut pellet_distances = DistanceField::new(&map);
+        pellet_distances.update_from_map(&map, map.pellets());
+        let enemies: Vec<Player> = serde_json::from_str(r#"[{"id":4,"x":8,"y":1}]"#).unwrap();
+
+        // The enemy has just walked off the pellet in the middle, towards the right
+        let context = Context { map: &map, distances: &distances, pellet_distances: &pellet_distances, me: Position::new(1, 1) };
+        let mut opponents = OpponentTracker::new();
+        opponents.observe(&context, &serde_json::from_str::<Vec<Player>>(r#"[{"id":4,"x":7,"y":1}]"#).unwrap());
+        opponents.observe(&context, &enemies);
+
+        let mut intents = IntentPredictor::new();
+        intents.update(&map, &distances, &enemies, &opponents);
+
+        let targets = intents.targets(4);
+        assert_eq!(Position::new(10, 1), targets[0].0);
+        assert!((targets.iter().map(|&(_, p)| p).sum::<f32>() - 1.0).abs() < 0.001);
+
+        assert!(intents.claim(4, &distances, &Position::new(11, 1)) > 0.8);
+        assert!(intents.claim(4, &distances, &Position::new(1, 1)) < 0.1);
+        assert_eq!(0.0, intents.claim(5, &distances, &Position::new(11, 1)));
+    }
+}
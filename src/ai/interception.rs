@@ -0,0 +1,137 @@
+use std::collections::HashSet;
+
+use common::{Direction, Position};
+use game::{DistanceTable, Map};
+
+const DIRECTIONS: [Direction; 4] = [Direction::Up, Direction::Down, Direction::Left, Direction::Right];
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Interception {
+    pub direction: Direction,
+    // Tiles the target can still get to before us once we've made the move, the ways it can escape
+    pub escape_tiles: usize,
+    // Ticks until the target is caught if it runs to the furthest of those tiles
+    pub ticks_to_catch: usize,
+}
+
+// Rather than walking after the target, which never catches anyone that runs as fast as we do, we move so that
+// the part of the map the target gets to before us is as small as possible. That cuts off its escape routes
+// until there is nowhere left to run.
+pub fn intercept(map: &Map, walkable: &HashSet<Position>, distances: &DistanceTable, me: &Position, target: &Position) -> Option<Interception> {
+    DIRECTIONS
+        .iter()
+        .map(|d| (d, me.adjacent(map, d)))
+        .filter(|(_, next)| map.tile_at(next).is_walkable())
+        .filter_map(|(d, next)| {
+            let to_target = distances.distance(&next, target)?;
+            let escape: Vec<usize> = walkable
+                .iter()
+                .filter_map(|t| match (distances.distance(target, t), distances.distance(&next, t)) {
+                    (Some(theirs), Some(ours)) if theirs < ours => Some(ours),
+                    (Some(_), None) => Some(usize::MAX - 1),
+                    _ => None,
+                })
+                .collect();
+
+            let interception = Interception {
+                direction: d.clone(),
+                escape_tiles: escape.len(),
+                ticks_to_catch: 1 + escape.into_iter().max().unwrap_or(to_target),
+            };
+            Some((interception, to_target))
+        })
+        .min_by_key(|&(ref i, to_target)| (i.escape_tiles, i.ticks_to_catch, to_target))
+        .map(|(i, _)| i)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ai::rollout::{Chaser, RolloutPolicy};
+    use game::{MapInformation, SimState};
+    use game::sim::Event;
+    use protocol::{GameState, Message};
+    use rand::{SeedableRng, XorShiftRng};
+    use std::str::FromStr;
+
+    // Three corridors joined at both ends, the target at one end and us at the other
+    const STATE: &str = r#"{"gamestate":{"map":{"content":["|||||||||||||","|___________|","|_|||||||||_|","|___________|","|_|||||||||_|","|___________|","|||||||||||||"],"height":7,"pelletsleft":0,"width":13},"others":[{"id":1,"isdangerous":false,"score":0,"x":1,"y":3}],"you":{"id":0,"isdangerous":true,"score":0,"x":11,"y":3}},"messagetype":"stateupdate"}"#;
+
+    // Plays out a chase where we hunt the target by picking moves with `hunter`, while every enemy runs away like
+    // `rollout::Chaser` does. Returns how many ticks it took to eat the target, if we got it within `max_ticks`.
+    fn simulate_chase<F>(root: &SimState, distances: &DistanceTable, target: usize, max_ticks: usize, hunter: &F, rng: &mut XorShiftRng) -> Option<usize>
+        where F: Fn(&SimState) -> Option<Direction> {
+        let mut sim = root.clone();
+        for tick in 1..max_ticks + 1 {
+            if !sim.me().is_dangerous() {
+                return None;
+            }
+
+            let mut moves = vec![hunter(&sim).unwrap_or(Direction::Up)];
+            for player in 1..sim.players.len() {
+                moves.push(Chaser.choose(&sim, player, distances, rng));
+            }
+
+            let caught = sim
+                .apply(&moves)
+                .into_iter()
+                .any(|e| e == Event::Kill { eater: 0, eaten: target });
+            if caught {
+                return Some(tick);
+            }
+        }
+
+        None
+    }
+
+    // Share of simulated chases that end with the target eaten
+    fn success_rate<F>(root: &SimState, distances: &DistanceTable, target: usize, max_ticks: usize, hunter: &F, chases: usize, rng: &mut XorShiftRng) -> f32
+        where F: Fn(&SimState) -> Option<Direction> {
+        let caught = (0..chases)
+            .filter(|_| simulate_chase(root, distances, target, max_ticks, hunter, rng).is_some())
+            .count();
+
+        caught as f32 / chases as f32
+    }
+
+    fn parse(message: &str) -> GameState {
+        match Message::from_str(message).unwrap() {
+            Message::Update { state } => state,
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn should_catch_more_often_than_walking_after_the_target() {
+        let state = parse(STATE);
+        let distances = DistanceTable::from_map(&state.map);
+        let map_information = MapInformation::from_map(&state.map);
+        let root = SimState::from_game_state(&state, 60);
+
+        let interceptor = |sim: &SimState| {
+            let target = &sim.players[1].position;
+            intercept(sim.map(), map_information.walkable_positions(), &distances, &sim.me().position, target).map(|i| i.direction)
+        };
+        let follower = |sim: &SimState| distances.next_hop(&sim.me().position, &sim.players[1].position);
+
+        let mut rng = XorShiftRng::from_seed([1, 2, 3, 4]);
+        let intercepted = success_rate(&root, &distances, 1, 60, &interceptor, 200, &mut rng);
+        let followed = success_rate(&root, &distances, 1, 60, &follower, 200, &mut rng);
+
+        let report = format!("caught {:.0}% of targets by intercepting, {:.0}% by following", intercepted * 100.0, followed * 100.0);
+        assert!(intercepted > 0.9, "{}", report);
+        assert!(intercepted > followed, "{}", report);
+    }
+
+    #[test]
+    fn should_cut_off_the_only_way_out() {
+        let state = parse(STATE);
+        let distances = DistanceTable::from_map(&state.map);
+        let map_information = MapInformation::from_map(&state.map);
+
+        // We're at the end of the corridor the target is in, staying in it keeps the target from getting out on our side
+        let interception = intercept(&state.map, map_information.walkable_positions(), &distances, &Position::new(11, 3), &Position::new(4, 3)).unwrap();
+        assert_eq!(Direction::Left, interception.direction);
+        assert!(interception.ticks_to_catch < 20);
+    }
+}
@@ -0,0 +1,90 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use ai::route_planner::RoutePlanner;
+use game::{DistanceTable, Map, MapInformation};
+
+// Everything we work out about a map that only depends on its layout
+#[derive(Clone)]
+pub struct MapAnalysis {
+    pub fingerprint: u64,
+    pub map_information: Rc<MapInformation>,
+    pub distances: Rc<DistanceTable>,
+    pub route_planner: Rc<RoutePlanner>,
+}
+
+impl MapAnalysis {
+    pub fn from_map(map: &Map) -> MapAnalysis {
+        let map_information = MapInformation::from_map(map);
+        MapAnalysis {
+            fingerprint: map.fingerprint(),
+            route_planner: Rc::new(RoutePlanner::from_map(map, &map_information)),
+            map_information: Rc::new(map_information),
+            distances: Rc::new(DistanceTable::from_map(map)),
+        }
+    }
+}
+
+// Analysing a map takes a while, so we hold on to what we've seen in case the server switches back to it
+pub struct MapAnalysisCache {
+    analyses: HashMap<u64, MapAnalysis>,
+}
+
+impl MapAnalysisCache {
+    pub fn new() -> MapAnalysisCache {
+        MapAnalysisCache {
+            analyses: HashMap::new(),
+        }
+    }
+
+    pub fn insert(&mut self, analysis: MapAnalysis) {
+        self.analyses.insert(analysis.fingerprint, analysis);
+    }
+
+    pub fn get_or_analyse(&mut self, map: &Map) -> MapAnalysis {
+        self.analyses
+            .entry(map.fingerprint())
+            .or_insert_with(|| MapAnalysis::from_map(map))
+            .clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ai::Bot;
+    use common::Position;
+    use protocol::{GameState, Message};
+    use std::str::FromStr;
+    use traits::HasDimensions;
+
+    fn parse(content: &str, width: u32, height: u32) -> GameState {
+        let message = format!(r#"{{"messagetype":"stateupdate","gamestate":{{"map":{{"content":[{}],"height":{},"width":{},"pelletsleft":1}},"you":{{"id":0,"x":1,"y":1}},"others":[]}}}}"#, content, height, width);
+        match Message::from_str(&message).unwrap() {
+            Message::Update { state } => state,
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn should_rebuild_analysis_when_the_map_changes() {
+        let corridor = || parse(r#""|||||||","|_____.|","|||||||""#, 7, 3);
+        let room = || parse(r#""|||||","|___|","|_|.|","|___|","|||||""#, 5, 5);
+
+        let mut bot = Bot::from_game_state(&corridor());
+        bot.determine_action(corridor());
+        let original = bot.map_information.clone();
+
+        // A new round on another map
+        bot.reset();
+        bot.determine_action(room());
+        assert_eq!((5, 5), (bot.map_information.width(), bot.map_information.height()));
+        assert_eq!(Some(2), bot.distances.distance(&Position::new(1, 1), &Position::new(3, 1)));
+        assert_eq!(2, bot.map_analyses.analyses.len());
+
+        // And back again, which we've seen before
+        bot.reset();
+        bot.determine_action(corridor());
+        assert!(Rc::ptr_eq(&original, &bot.map_information));
+    }
+}
@@ -1,19 +1,67 @@
 use std::cell::RefCell;
+use std::rc::Rc;
 
+mod arbiter;
+pub mod config;
+mod danger;
+pub mod death;
+mod distance_field;
+mod intent;
+mod interception;
+mod map_analysis;
+mod opponents;
+mod oscillation;
 mod pathfinder;
+mod prediction;
+mod rollout;
+mod route_planner;
+mod safety;
 mod strategies;
+mod territory;
+pub mod trace;
 
+use ai::arbiter::{Breakdown, Policy, PriorityPolicy, Proposal, WeightedPolicy};
+use ai::config::BotConfig;
 use ai::strategies::Strategy;
 use common::{Direction, Position, rules};
 use game;
 use protocol;
 use traits::HasPosition;
 
+// Paths through tiles more dangerous than this are not considered safe at all
+const MAX_ACCEPTABLE_DANGER: u32 = 50;
+
+// How many ticks ahead we try to predict where enemies might be
+const PREDICTION_HORIZON: usize = 3;
+
+// Extra ticks we give ourselves to get to a target we've committed to
+const COMMITMENT_SLACK: u32 = 5;
+
+// Whether an enemy can eat us, or at least get in our way. Only the dangerous ones matter while we can eat others.
+pub fn is_threat(enemy: &protocol::Player, can_eat_others: bool) -> bool {
+    !can_eat_others || enemy.is_dangerous
+}
+
 pub struct Bot {
-    map_information: game::MapInformation,
+    map_information: Rc<game::MapInformation>,
+    distances: Rc<game::DistanceTable>,
     path_graph: pathfinder::LocalPathGraph,
+    territory: territory::Territory,
+    // Distance to the closest enemy that can eat us, and to the closest pellet
+    threat_distances: distance_field::DistanceField,
+    pellet_distances: distance_field::DistanceField,
+    opponents: opponents::OpponentTracker,
+    intents: intent::IntentPredictor,
+    danger: danger::EnemyDangerField,
+    route_planner: Rc<route_planner::RoutePlanner>,
+    map_fingerprint: u64,
+    map_analyses: map_analysis::MapAnalysisCache,
+    oscillation: oscillation::OscillationDetector,
+    commitment: Option<oscillation::Commitment>,
 
     strategies: Vec<RefCell<Box<Strategy>>>,
+    policy: Box<dyn Policy>,
+    tracer: Option<trace::Tracer>,
 
     previous_strategy_type: Option<strategies::StrategyType>,
     previous_state: Option<protocol::GameState>,
@@ -24,19 +72,52 @@ pub struct Bot {
 
     tick: u32,
     remaining_ticks_dangerous: u32,
+
+    // This round's deaths
+    deaths: Vec<death::Death>,
 }
 
 impl Bot {
+    #[cfg(test)]
     pub fn from_game_state(state: &protocol::GameState) -> Bot {
+        Bot::with_config(state, &BotConfig::default())
+    }
+
+    pub fn with_config(state: &protocol::GameState, config: &BotConfig) -> Bot {
+        let weighted: Vec<(Box<dyn Strategy>, f32)> = config
+            .enabled_strategies()
+            .iter()
+            .filter_map(|s| strategies::create(&s.name, &s.params).map(|strategy| (strategy, s.weight)))
+            .collect();
+        let policy: Box<dyn Policy> = match config.policy.as_str() {
+            "weighted" => Box::new(WeightedPolicy::new(weighted.iter().map(|(s, w)| (s.description(), *w)).collect())),
+            _ => Box::new(PriorityPolicy::new(weighted.iter().map(|(s, _)| s.description()).collect())),
+        };
+        let strategies: Vec<Box<dyn Strategy>> = weighted.into_iter().map(|(s, _)| s).collect();
+
+        let analysis = map_analysis::MapAnalysis::from_map(&state.map);
+        let mut map_analyses = map_analysis::MapAnalysisCache::new();
+        map_analyses.insert(analysis.clone());
+
         Bot {
-            map_information: game::MapInformation::from_map(&state.map),
+            map_information: analysis.map_information,
+            distances: analysis.distances,
             path_graph: pathfinder::LocalPathGraph::new(&state.map),
+            territory: territory::Territory::new(&state.map),
+            threat_distances: distance_field::DistanceField::new(&state.map),
+            pellet_distances: distance_field::DistanceField::new(&state.map),
+            opponents: opponents::OpponentTracker::new(),
+            intents: intent::IntentPredictor::new(),
+            danger: danger::EnemyDangerField::new(),
+            route_planner: analysis.route_planner,
+            map_fingerprint: analysis.fingerprint,
+            map_analyses,
+            oscillation: oscillation::OscillationDetector::new(),
+            commitment: None,
 
-            strategies: vec![
-                RefCell::new(Box::new(strategies::Avoidance::new())),
-                RefCell::new(Box::new(strategies::Hunter::new())),
-                RefCell::new(Box::new(strategies::PickPellets::new())),
-            ],
+            strategies: strategies.into_iter().map(RefCell::new).collect(),
+            policy,
+            tracer: None,
 
             previous_strategy_type: None,
             previous_state: None,
@@ -47,14 +128,31 @@ impl Bot {
 
             tick: 0,
             remaining_ticks_dangerous: 0,
+
+            deaths: Vec::new(),
         }
     }
 
     pub fn determine_action(&mut self, state: protocol::GameState) -> Direction {
+        // Ending up somewhere we couldn't have walked to means we died and respawned, even if nobody told us
+        let respawned = self.previous_state.as_ref().map(|previous| {
+            let from = previous.me.position();
+            from != state.me.position() && !from.neighbours(&state.map).contains(&state.me.position())
+        });
+        if respawned == Some(true) {
+            self.on_death();
+        }
+
+        // The server may have switched maps since the last round
+        if self.tick == 0 {
+            self.update_map_analysis(&state.map);
+        }
+
         self.tick += 1;
 
         // Run BFS on map to get pathing information
         self.path_graph.update_from_map(&state.map, &state.me.position());
+        self.territory.update_from_map(&state.map, &state.me.position(), &state.enemies);
 
         // Set some state based on what tile we landed on
         if self.expected_tile_type == game::TileType::SuperPellet {
@@ -62,30 +160,122 @@ impl Bot {
             self.remaining_ticks_dangerous = rules::TICKS_DANGEROUS + 1;
         }
 
+        // Learn from how the enemies moved, before the distance fields move on to this tick
+        if let Some(ref previous) = self.previous_state {
+            let context = opponents::Context {
+                map: &previous.map,
+                distances: &self.distances,
+                pellet_distances: &self.pellet_distances,
+                me: previous.me.position(),
+            };
+            self.opponents.observe(&context, &state.enemies);
+        }
+
+        self.opponents.track_danger(&state.enemies);
+        self.update_distance_fields(&state);
+        self.intents.update(&state.map, &self.distances, &state.enemies, &self.opponents);
+        self.danger.update(&self.map_information, &self.distances, &state.enemies, self.can_eat_others());
+
         // Some asserts that our internal state matches what the server sends
         debug_assert_eq!(state.me.is_dangerous, self.can_eat_others());
 
-        let action = self.strategies
-            .iter()
-            .map(|x| (x.borrow_mut().action(&self, &state), x))
-            .find(|&(ref d, _)| d.is_some());
-
-        let decision = match action {
-            Some((d, a)) => {
-                let action = a.borrow();
-                if self.previous_strategy_type != Some(action.description()) {
-                    // println!("Switched strategy to: {:?}", action.description());
-                    self.previous_strategy_type = Some(action.description());
+        // Every strategy gets to propose something, the policy decides who wins
+        let mut proposals = Vec::new();
+        for strategy in &self.strategies {
+            let mut strategy = strategy.borrow_mut();
+            let description = strategy.description();
+            proposals.extend(strategy
+                .candidates(self, &state)
+                .into_iter()
+                .map(|candidate| Proposal { strategy: description, candidate }));
+        }
+
+        let (winner, explanation) = self.policy.choose(&proposals);
+        let mut breakdown = Breakdown {
+            tick: self.tick,
+            policy: self.policy.name(),
+            proposals,
+            winner,
+            explanation,
+        };
+
+        let decision = match breakdown.winning_proposal() {
+            Some(proposal) => {
+                if self.previous_strategy_type != Some(proposal.strategy) {
+                    debug!("Switched strategy to {:?}: {}", proposal.strategy, proposal.candidate.reason);
+                    self.previous_strategy_type = Some(proposal.strategy);
                 }
 
-                d.unwrap()
+                proposal.candidate.direction.clone()
             },
             None => {
-                println!("FALLBACK MOVEMENT");
+                warn!("No strategy proposed anything, falling back to going {}", self.previous_direction);
                 self.previous_direction.clone()
             }
         };
 
+        // Strategies taking turns can keep us going back and forth, or waiting for a way that never opens up, so
+        // once that happens we pick a target and stick with it until we get there
+        self.oscillation.record(oscillation::Step {
+            position: state.me.position(),
+            strategy: breakdown.winning_proposal().map(|p| p.strategy),
+            direction: decision.clone(),
+            target: breakdown.winning_proposal().and_then(|p| p.candidate.rationale.target.clone()),
+        });
+        if self.commitment.is_none() {
+            if let Some(period) = self.oscillation.period() {
+                self.commitment = self.pellet_distances
+                    .closest_source_to(&state.me.position())
+                    .and_then(|target| self.safest_path_to(&state, target).map(|path| (target, path.len())))
+                    .map(|(target, distance)| oscillation::Commitment {
+                        target: target.clone(),
+                        expires: self.tick + 2 * distance as u32 + COMMITMENT_SLACK,
+                    });
+
+                if let Some(ref commitment) = self.commitment {
+                    info!("Stuck in a cycle of {} ticks at {} between {:?}, committing to {}", period, state.me.position(), self.oscillation.strategies(), commitment.target);
+                }
+                self.oscillation.reset();
+            }
+        }
+
+        let mut proposer = breakdown.winning_proposal().map(|p| format!("{:?}", p.strategy)).unwrap_or_else(|| "Fallback".to_string());
+        let mut waits = breakdown.winning_proposal().map(|p| p.candidate.rationale.waits).unwrap_or(false);
+        let decision = match self.follow_commitment(&state, &breakdown.proposals) {
+            Some(direction) => {
+                if direction != decision {
+                    breakdown.explanation = format!("{}, overruled to keep going for {}", breakdown.explanation, self.commitment.as_ref().unwrap().target);
+                    proposer = "Commitment".to_string();
+                    waits = false;
+                }
+                direction
+            },
+            None => decision,
+        };
+
+        // Whoever came up with the move, it has to get past this
+        let veto = {
+            let check = safety::SafetyCheck::new(&state.map, &self.map_information, &self.distances, state.me.position(), &state.enemies, self.can_eat_others());
+            safety::veto(&check, &decision, waits, &breakdown.proposals, &*self.policy)
+        };
+        let decision = match veto {
+            Some(veto) => {
+                info!("Vetoed {} from {}, it {}, going {} instead", decision, proposer, veto.hazard, veto.replacement);
+                breakdown.explanation = format!("{}, vetoed because it {}", breakdown.explanation, veto.hazard);
+                veto.replacement
+            },
+            None => decision,
+        };
+
+        let remaining_ticks_dangerous = self.remaining_ticks_dangerous;
+        let opponents = &self.opponents;
+        let traced = self.tracer.as_mut().map(|t| t.record(&state, opponents, remaining_ticks_dangerous, &breakdown, &decision));
+        if let Some(Err(e)) = traced {
+            error!("Failed to write trace, no longer tracing ({})", e);
+            self.tracer = None;
+        }
+        trace!("{}", breakdown);
+
         if self.previous_direction != decision {
             self.previous_direction = decision.clone();
         }
@@ -98,15 +288,131 @@ impl Bot {
         decision
     }
 
+    // Everything we know about the map is wrong after a change of layout, so it's either looked up or worked out again
+    fn update_map_analysis(&mut self, map: &game::Map) {
+        if map.fingerprint() == self.map_fingerprint {
+            return;
+        }
+
+        info!("Map layout changed, updating map analysis");
+        let analysis = self.map_analyses.get_or_analyse(map);
+        self.map_information = analysis.map_information;
+        self.distances = analysis.distances;
+        self.route_planner = analysis.route_planner;
+        self.map_fingerprint = analysis.fingerprint;
+
+        self.path_graph = pathfinder::LocalPathGraph::new(map);
+        self.territory = territory::Territory::new(map);
+        self.threat_distances = distance_field::DistanceField::new(map);
+        self.pellet_distances = distance_field::DistanceField::new(map);
+    }
+
     pub fn reset(&mut self) {
+        self.reset_life();
+        self.tick = 0;
+        self.deaths.clear();
+    }
+
+    // Figures out what got us killed, and forgets everything that only made sense while we were alive. Whether
+    // we respawn or have to wait for the next round, the next state update starts from scratch.
+    pub fn on_death(&mut self) {
+        let death = self.previous_state.as_ref().map(|last| {
+            death::Death::from_last_state(self.tick, last, &self.previous_direction, &self.distances, self.previous_strategy_type, self.can_eat_others())
+        });
+
+        if let Some(death) = death {
+            info!("{}", death);
+            self.deaths.push(death);
+        }
+
+        self.reset_life();
+    }
+
+    pub fn deaths(&self) -> &[death::Death] {
+        &self.deaths
+    }
+
+    fn reset_life(&mut self) {
         self.previous_state = None;
+        self.previous_strategy_type = None;
+        self.expected_tile_type = game::TileType::Floor;
         self.current_destination = None;
         self.previous_direction = Direction::Down;
-        self.tick = 0;
         self.remaining_ticks_dangerous = 0;
+        self.oscillation.reset();
+        self.commitment = None;
+
+        for strategy in &self.strategies {
+            strategy.borrow_mut().reset();
+        }
+    }
+
+    // Next step along the safest way to the target we committed to, until we get there or it's no longer safe to
+    // go on. Running from an enemy or out of the spawn another way always comes first, as does anything the safety
+    // check has against the step.
+    fn follow_commitment(&mut self, state: &protocol::GameState, proposals: &[Proposal]) -> Option<Direction> {
+        let me = state.me.position();
+        let direction = match self.commitment {
+            Some(ref c) if me != c.target && state.map.tile_at(&c.target).is_pellet() && self.tick <= c.expires => {
+                self.safest_path_to(state, &c.target).and_then(|path| pathfinder::first_direction(&state.map, &me, &path))
+            },
+            _ => None,
+        };
+
+        let escaping = proposals
+            .iter()
+            .filter(|p| p.strategy == strategies::StrategyType::Avoidance || p.strategy == strategies::StrategyType::SpawnEscape)
+            .any(|p| Some(&p.candidate.direction) != direction.as_ref());
+        let unsafe_step = direction.as_ref().is_some_and(|d| {
+            let check = safety::SafetyCheck::new(&state.map, &self.map_information, &self.distances, me.clone(), &state.enemies, self.can_eat_others());
+            check.hazard(d, false).is_some()
+        });
+
+        if direction.is_none() || escaping || unsafe_step {
+            if let (true, Some(c)) = (direction.is_some(), self.commitment.as_ref()) {
+                debug!("Dropping commitment to {}", c.target);
+            }
+            self.commitment = None;
+            return None;
+        }
+
+        direction
+    }
+
+    pub fn set_tracer(&mut self, tracer: trace::Tracer) {
+        self.tracer = Some(tracer);
     }
 
     pub fn can_eat_others(&self) -> bool {
         self.remaining_ticks_dangerous > 0
     }
+
+    fn update_distance_fields(&mut self, state: &protocol::GameState) {
+        let can_eat_others = self.can_eat_others();
+        let threats = state.enemies
+            .iter()
+            .filter(|e| is_threat(e, can_eat_others))
+            .map(|e| e.position());
+
+        self.threat_distances.update_from_map(&state.map, threats);
+        self.pellet_distances.update_from_map(&state.map, state.map.pellets());
+    }
+
+    // Cheapest path when both distance and closeness to enemies are taken in to account
+    pub fn safest_path_to(&self, state: &protocol::GameState, to: &Position) -> Option<Vec<Position>> {
+        pathfinder::get_safest(&state.map, &state.me.position(), to, &self.danger, MAX_ACCEPTABLE_DANGER)
+    }
+
+    // Shortest path that stays out of reach of enemies that can hurt us, given where they might move
+    pub fn predicted_safe_path_to(&self, state: &protocol::GameState, to: &Position) -> Option<Vec<Position>> {
+        let threats: Vec<&protocol::Player> = state.enemies
+            .iter()
+            .filter(|e| is_threat(e, self.can_eat_others()))
+            .collect();
+
+        let predictor = prediction::EnemyReachPredictor::new(&self.distances, &threats, PREDICTION_HORIZON);
+        self.distances
+            .distance(&state.me.position(), to)
+            .and_then(|d| pathfinder::get_shortest_timed(&state.map, &state.me.position(), to, &predictor, d + PREDICTION_HORIZON * 2))
+    }
 }
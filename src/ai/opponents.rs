@@ -0,0 +1,286 @@
+use std::collections::HashMap;
+
+use ai::distance_field::DistanceField;
+use common::{Direction, Position, rules};
+use game::{DistanceTable, Map};
+use protocol::Player;
+use traits::HasPosition;
+
+const DIRECTIONS: [Direction; 4] = [Direction::Up, Direction::Down, Direction::Left, Direction::Right];
+
+// Chance that a player does something other than what its behaviour says, nobody plays perfectly
+const NOISE: f32 = 0.1;
+
+// How much of the old evidence is kept each tick, so that we notice when someone changes their mind
+const DECAY: f32 = 0.95;
+
+// A behaviour has to explain this much of what a player does before it's classified as that
+const CLASSIFICATION_THRESHOLD: f32 = 0.6;
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Serialize)]
+pub enum Behaviour {
+    PelletEater,
+    Chaser,
+    RandomWalker,
+    Avoider,
+}
+
+const BEHAVIOURS: [Behaviour; 4] = [Behaviour::PelletEater, Behaviour::Chaser, Behaviour::RandomWalker, Behaviour::Avoider];
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub enum Classification {
+    Pure(Behaviour),
+    Mixed,
+}
+
+// What the behaviours are judged against
+pub struct Context<'a> {
+    pub map: &'a Map,
+    pub distances: &'a DistanceTable,
+    pub pellet_distances: &'a DistanceField,
+    // Chasers chase, and avoiders avoid, us
+    pub me: Position,
+}
+
+impl<'a> Context<'a> {
+    // Probability of each legal move if the player only followed the given behaviour
+    fn move_probabilities(&self, behaviour: Behaviour, position: &Position, last_direction: Option<&Direction>) -> Vec<(Direction, f32)> {
+        let moves: Vec<(Direction, Position)> = DIRECTIONS
+            .iter()
+            .map(|d| (d.clone(), position.adjacent(self.map, d)))
+            .filter(|(_, p)| self.map.tile_at(p).is_walkable())
+            .collect();
+
+        if moves.is_empty() {
+            return Vec::new();
+        }
+
+        // Lower is better, None if the behaviour doesn't care
+        let cost = |p: &Position| -> Option<i64> {
+            match behaviour {
+                Behaviour::PelletEater => self.pellet_distances.distance_at(p).map(|d| d as i64),
+                Behaviour::Chaser => self.distances.distance(p, &self.me).map(|d| d as i64),
+                Behaviour::Avoider => self.distances.distance(p, &self.me).map(|d| -(d as i64)),
+                Behaviour::RandomWalker => None,
+            }
+        };
+
+        let best = moves.iter().filter_map(|(_, p)| cost(p)).min();
+        let preferred: Vec<bool> = moves
+            .iter()
+            .map(|(d, p)| match (best, cost(p)) {
+                (Some(b), Some(c)) => c == b,
+                // Random walkers keep going the way they were going
+                _ => last_direction.map(|l| !d.is_opposite_to(l)).unwrap_or(true),
+            })
+            .collect();
+
+        let preferred_count = preferred.iter().filter(|&&p| p).count().max(1) as f32;
+        let noise = NOISE / moves.len() as f32;
+        moves
+            .into_iter()
+            .zip(preferred)
+            .map(|((d, _), p)| (d, noise + if p { (1.0 - NOISE) / preferred_count } else { 0.0 }))
+            .collect()
+    }
+}
+
+struct OpponentHistory {
+    position: Position,
+    last_direction: Option<Direction>,
+
+    // Decayed log-likelihood of what the player has done under each behaviour
+    log_likelihoods: [f32; 4],
+    observations: usize,
+}
+
+impl OpponentHistory {
+    // How much each behaviour explains the moves seen so far, sums to 1
+    fn weights(&self) -> [f32; 4] {
+        let max = self.log_likelihoods.iter().cloned().fold(f32::MIN, f32::max);
+        let mut weights = [0.0; 4];
+        for (w, l) in weights.iter_mut().zip(&self.log_likelihoods) {
+            *w = (l - max).exp();
+        }
+
+        let total: f32 = weights.iter().sum();
+        for w in &mut weights {
+            *w /= total;
+        }
+        weights
+    }
+}
+
+// Learns how each enemy plays from how they have moved so far
+pub struct OpponentTracker {
+    opponents: HashMap<u32, OpponentHistory>,
+    // Ticks until each enemy can be eaten again
+    dangerous_ticks: HashMap<u32, u32>,
+}
+
+impl OpponentTracker {
+    pub fn new() -> OpponentTracker {
+        OpponentTracker {
+            opponents: HashMap::new(),
+            dangerous_ticks: HashMap::new(),
+        }
+    }
+
+    // The server only tells whether enemies are dangerous, so count down from when they became dangerous. When we
+    // didn't see that happen we assume the worst.
+    pub fn track_danger(&mut self, enemies: &[Player]) {
+        for enemy in enemies {
+            let ticks = self.dangerous_ticks.entry(enemy.id).or_default();
+            *ticks = match (enemy.is_dangerous, *ticks) {
+                (false, _) => 0,
+                (true, 0) => rules::TICKS_DANGEROUS,
+                (true, t) => (t - 1).max(1),
+            };
+        }
+    }
+
+    pub fn remaining_dangerous_ticks(&self, id: u32) -> u32 {
+        self.dangerous_ticks.get(&id).cloned().unwrap_or(0)
+    }
+
+    // The context has to describe the tick before, since that's what the enemies based their moves on
+    pub fn observe(&mut self, previous: &Context, enemies: &[Player]) {
+        for enemy in enemies {
+            let position = enemy.position();
+            let history = self.opponents.entry(enemy.id).or_insert_with(|| OpponentHistory {
+                position: position.clone(),
+                last_direction: None,
+                log_likelihoods: [0.0; 4],
+                observations: 0,
+            });
+
+            // Standing still, or respawning, doesn't say anything about how someone plays
+            let direction = DIRECTIONS
+                .iter()
+                .find(|d| history.position.adjacent(previous.map, d) == position)
+                .cloned();
+
+            if let Some(direction) = direction {
+                for (i, behaviour) in BEHAVIOURS.iter().enumerate() {
+                    let probability = previous
+                        .move_probabilities(*behaviour, &history.position, history.last_direction.as_ref())
+                        .into_iter()
+                        .find(|(d, _)| *d == direction)
+                        .map(|(_, p)| p)
+                        .unwrap_or(NOISE);
+                    history.log_likelihoods[i] = DECAY * history.log_likelihoods[i] + probability.ln();
+                }
+
+                history.observations += 1;
+                history.last_direction = Some(direction);
+            }
+            else {
+                history.last_direction = None;
+            }
+
+            history.position = position;
+        }
+    }
+
+    pub fn classify(&self, id: u32) -> Option<Classification> {
+        self.opponents.get(&id).filter(|h| h.observations > 0).map(|h| {
+            let weights = h.weights();
+            match BEHAVIOURS.iter().zip(&weights).find(|&(_, &w)| w >= CLASSIFICATION_THRESHOLD) {
+                Some((b, _)) => Classification::Pure(*b),
+                None => Classification::Mixed,
+            }
+        })
+    }
+
+    // Probability of each move an enemy can make from the given position, a mix of the behaviours weighted by
+    // how well they have explained the enemy so far
+    pub fn predict(&self, id: u32, context: &Context, position: &Position, last_direction: Option<&Direction>) -> Vec<(Direction, f32)> {
+        let weights = match self.opponents.get(&id) {
+            Some(h) => h.weights(),
+            None => [0.25; 4],
+        };
+
+        let mut probabilities: Vec<(Direction, f32)> = Vec::with_capacity(4);
+        for (behaviour, weight) in BEHAVIOURS.iter().zip(&weights) {
+            for (d, p) in context.move_probabilities(*behaviour, position, last_direction) {
+                match probabilities.iter().position(|(x, _)| *x == d) {
+                    Some(i) => probabilities[i].1 += weight * p,
+                    None => probabilities.push((d, weight * p)),
+                }
+            }
+        }
+
+        probabilities
+    }
+
+    // The direction the enemy moved in last tick, if it moved
+    pub fn last_direction(&self, id: u32) -> Option<&Direction> {
+        self.opponents.get(&id).and_then(|h| h.last_direction.as_ref())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json;
+
+    const MAP: &str = r#"
+{
+    "content": [
+        "|||||||||||",
+        "|.........|",
+        "|_|_|_|_|_|",
+        "|_________|",
+        "|||||||||||"
+    ],
+    "height": 5,
+    "pelletsleft": 9,
+    "width": 11
+}"#;
+
+    fn enemy(x: u32, y: u32) -> Player {
+        serde_json::from_str(&format!(r#"{{"id":7,"x":{},"y":{}}}"#, x, y)).unwrap()
+    }
+
+    #[test]
+    fn can_classify_chaser() {
+        let map: Map = serde_json::from_str(MAP).unwrap();
+        let distances = DistanceTable::from_map(&map);
+        let mut pellet_distances = DistanceField::new(&map);
+        pellet_distances.update_from_map(&map, map.pellets());
+
+        // We sit still in the bottom left corner while the enemy comes straight at us along the empty corridor
+        let context = Context { map: &map, distances: &distances, pellet_distances: &pellet_distances, me: Position::new(1, 3) };
+        let mut tracker = OpponentTracker::new();
+        for x in (1..10).rev() {
+            tracker.observe(&context, &[enemy(x, 3)]);
+        }
+
+        assert_eq!(Some(Classification::Pure(Behaviour::Chaser)), tracker.classify(7));
+        assert_eq!(None, tracker.classify(8));
+
+        let prediction = tracker.predict(7, &context, &Position::new(3, 3), Some(&Direction::Left));
+        let left = prediction.iter().find(|&(d, _)| *d == Direction::Left).unwrap().1;
+        assert!(left > 0.8);
+        assert!((prediction.iter().map(|&(_, p)| p).sum::<f32>() - 1.0).abs() < 0.001);
+    }
+
+    #[test]
+    fn can_classify_pellet_eater() {
+        let map: Map = serde_json::from_str(MAP).unwrap();
+        let distances = DistanceTable::from_map(&map);
+
+        // The enemy eats its way along the top corridor, away from us
+        let mut tracker = OpponentTracker::new();
+        for x in 1..10 {
+            let mut remaining = map.pellets();
+            remaining.retain(|p| p.x >= x);
+            let mut pellet_distances = DistanceField::new(&map);
+            pellet_distances.update_from_map(&map, remaining);
+            let context = Context { map: &map, distances: &distances, pellet_distances: &pellet_distances, me: Position::new(5, 3) };
+            tracker.observe(&context, &[enemy(x, 1)]);
+        }
+
+        assert_eq!(Some(Classification::Pure(Behaviour::PelletEater)), tracker.classify(7));
+    }
+}
@@ -0,0 +1,208 @@
+use std::collections::VecDeque;
+
+use ai::strategies::StrategyType;
+use common::{Direction, Position};
+
+// How many times a cycle has to repeat before we call it oscillating
+const REPETITIONS: usize = 3;
+
+// Longest cycle we look for, in ticks
+const MAX_PERIOD: usize = 4;
+
+// A target we hold on to, whatever the strategies say, to get out of a loop
+pub struct Commitment {
+    pub target: Position,
+    // Tick after which we give up on getting there
+    pub expires: u32,
+}
+
+// Where we were on a tick, and which strategy sent us where
+#[derive(Clone, Debug, PartialEq)]
+pub struct Step {
+    pub position: Position,
+    // None when no strategy proposed anything
+    pub strategy: Option<StrategyType>,
+    pub direction: Direction,
+    // Where the strategy was trying to get to
+    pub target: Option<Position>,
+}
+
+// Remembers where we've been and what we decided there, to notice when we keep making the same few decisions
+// without getting anywhere
+pub struct OscillationDetector {
+    steps: VecDeque<Step>,
+}
+
+impl OscillationDetector {
+    pub fn new() -> OscillationDetector {
+        OscillationDetector {
+            steps: VecDeque::with_capacity(MAX_PERIOD * REPETITIONS),
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.steps.clear();
+    }
+
+    pub fn record(&mut self, step: Step) {
+        if self.steps.len() == MAX_PERIOD * REPETITIONS {
+            self.steps.pop_front();
+        }
+
+        self.steps.push_back(step);
+    }
+
+    // The cycle we're stuck in, if any. Making the same decision in the same place is waiting rather than
+    // oscillating, unless we're waiting to get somewhere else and it goes on for as long as the longest cycle.
+    fn cycle(&self) -> Option<Vec<&Step>> {
+        (1..MAX_PERIOD + 1)
+            .filter_map(|period| {
+                let length = if period == 1 { MAX_PERIOD * REPETITIONS } else { period * REPETITIONS };
+                if self.steps.len() < length {
+                    return None;
+                }
+
+                let recent: Vec<&Step> = self.steps.iter().skip(self.steps.len() - length).collect();
+                let cycle = &recent[..period];
+                let changing = cycle.iter().any(|s| s.position != cycle[0].position || s.direction != cycle[0].direction);
+                let stalled = period == 1 && cycle[0].target.as_ref().is_some_and(|t| *t != cycle[0].position);
+                if (changing || stalled) && recent.chunks(period).all(|chunk| chunk == cycle) { Some(cycle.to_vec()) } else { None }
+            })
+            .next()
+    }
+
+    // Length of the cycle we're stuck in, in ticks
+    pub fn period(&self) -> Option<usize> {
+        self.cycle().map(|c| c.len())
+    }
+
+    // The strategies taking turns in the cycle we're stuck in
+    pub fn strategies(&self) -> Vec<StrategyType> {
+        let mut strategies: Vec<StrategyType> = Vec::new();
+        for strategy in self.cycle().unwrap_or_default().into_iter().filter_map(|s| s.strategy) {
+            if !strategies.contains(&strategy) {
+                strategies.push(strategy);
+            }
+        }
+        strategies
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ai::Bot;
+    use common::Direction;
+    use protocol::{GameState, Message};
+    use std::str::FromStr;
+
+    // A wide corridor with a pellet on the far side of an enemy that doesn't move
+    fn corridor(me: u32) -> GameState {
+        let message = format!(r#"{{"messagetype":"stateupdate","gamestate":{{"map":{{"content":["|||||||||||||||||||||","|______________.____|","|___________________|","|||||||||||||||||||||"],"height":4,"width":21,"pelletsleft":1}},"you":{{"id":0,"x":{},"y":1}},"others":[{{"id":1,"x":10,"y":2}}]}}}}"#, me);
+        match Message::from_str(&message).unwrap() {
+            Message::Update { state } => state,
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn should_give_up_commitments_to_run_from_enemies() {
+        let mut bot = Bot::from_game_state(&corridor(4));
+        bot.tick = 10;
+        bot.commitment = Some(Commitment { target: Position::new(15, 1), expires: 100 });
+        for me in 4..8 {
+            assert_eq!(Direction::Right, bot.determine_action(corridor(me)));
+            assert!(bot.commitment.is_some());
+        }
+
+        // Close enough to the enemy that the next step isn't safe, which beats getting to the pellet
+        assert_ne!(Direction::Right, bot.determine_action(corridor(8)));
+        assert!(bot.commitment.is_none());
+    }
+
+    fn step(x: u32, strategy: StrategyType, direction: Direction) -> Step {
+        Step {
+            position: Position::new(x, 1),
+            strategy: Some(strategy),
+            direction,
+            target: None,
+        }
+    }
+
+    #[test]
+    fn can_detect_going_back_and_forth() {
+        let mut detector = OscillationDetector::new();
+        for x in 0..5 {
+            detector.record(step(x, StrategyType::PickPellets, Direction::Right));
+        }
+        assert_eq!(None, detector.period());
+
+        for _ in 0..3 {
+            detector.record(step(5, StrategyType::PickPellets, Direction::Right));
+            detector.record(step(6, StrategyType::Avoidance, Direction::Left));
+        }
+        assert_eq!(Some(2), detector.period());
+        assert_eq!(vec![StrategyType::PickPellets, StrategyType::Avoidance], detector.strategies());
+
+        // Waiting in the same place is fine
+        detector.reset();
+        for _ in 0..6 {
+            detector.record(step(5, StrategyType::PickPellets, Direction::Up));
+        }
+        assert_eq!(None, detector.period());
+
+        // Unless we're waiting to get somewhere else for longer than any cycle would take
+        for _ in 0..MAX_PERIOD * REPETITIONS {
+            detector.record(Step { target: Some(Position::new(9, 1)), ..step(5, StrategyType::PickPellets, Direction::Up) });
+        }
+        assert_eq!(Some(1), detector.period());
+
+        // But not changing our mind about which way to go while we're stuck there
+        for _ in 0..3 {
+            detector.record(step(5, StrategyType::PickPellets, Direction::Up));
+            detector.record(step(5, StrategyType::Killer, Direction::Down));
+        }
+        assert_eq!(Some(2), detector.period());
+
+        // Ending up in the same places for different reasons each time around is not a cycle
+        detector.reset();
+        for strategy in &[StrategyType::PickPellets, StrategyType::Killer, StrategyType::Avoidance] {
+            detector.record(step(5, *strategy, Direction::Right));
+            detector.record(step(6, StrategyType::Avoidance, Direction::Left));
+        }
+        assert_eq!(None, detector.period());
+    }
+
+    fn ring_at(me: (u32, u32), eaten: &[u32]) -> GameState {
+        let top: String = (1..20).map(|x| if x >= 13 && !eaten.contains(&x) { '.' } else { '_' }).collect();
+        let message = format!(r#"{{"messagetype":"stateupdate","gamestate":{{"map":{{"content":["|||||||||||||||||||||","|{}|","|_|||||||||||||||||_|","|___________________|","|||||||||||||||||||||"],"height":5,"width":21,"pelletsleft":7}},"you":{{"id":0,"x":{},"y":{}}},"others":[{{"id":1,"x":10,"y":1}}]}}}}"#, top, me.0, me.1);
+        match Message::from_str(&message).unwrap() {
+            Message::Update { state } => state,
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn should_not_get_stuck_on_the_way_to_pellets() {
+        // Going over the top means waiting for the enemy to move, which it never does
+        let mut bot = Bot::from_game_state(&ring_at((1, 1), &[]));
+        let mut me = Position::new(1, 1);
+        let mut eaten = Vec::new();
+        let mut committed = false;
+        for _ in 0..60 {
+            let direction = bot.determine_action(ring_at((me.x, me.y), &eaten));
+            let state = ring_at((me.x, me.y), &eaten);
+            let next = me.adjacent(&state.map, &direction);
+            if state.map.tile_at(&next).is_walkable() {
+                me = next;
+            }
+            if state.map.tile_at(&me).is_pellet() {
+                eaten.push(me.x);
+            }
+            committed |= bot.commitment.is_some();
+        }
+
+        assert!(committed);
+        assert_eq!(7, eaten.len());
+    }
+}
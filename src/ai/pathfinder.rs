@@ -1,10 +1,11 @@
-use pathfinding::{astar, bfs};
 use std::collections::{HashMap, VecDeque};
 
-use common::Position;
+use ai::danger::DangerField;
+use ai::prediction::OccupancyPredictor;
+use common::{Direction, Position};
 use game;
+pub use game::PathQuery;
 use protocol::Player;
-use traits::HasPosition;
 
 pub struct LocalPathGraph {
     nodes: HashMap<Position, TilePathInformation>,
@@ -75,38 +76,61 @@ impl LocalPathGraph {
 // This method uses breadth-first search to find the pellet closest to our position
 pub fn find_closest_pellet<F>(map: &game::Map, origin: &Position, enemies: &[Player], extra_filter: F) -> Option<Vec<Position>>
     where F: Fn(&Position) -> bool {
-    let path = bfs(origin
-        , |p| p.neighbours(map).into_iter().filter(|x| map.tile_at(&x).is_walkable() && enemies.iter().find(|e| e.position() == *x).is_none() && extra_filter(x))
-        , |p| map.tile_at(&p).is_pellet() && extra_filter(p));
-
-    if let Some(x) = path {
-        return Some(x
-            .into_iter()
-            .skip(1)
-            .rev()
-            .collect())
-    }
-
-    None
+    let extra_filter = &extra_filter;
+    PathQuery::new(map, origin)
+        .nearest(move |p| map.tile_at(p).is_pellet() && extra_filter(p))
+        .blocked_by(move |p| !extra_filter(p))
+        .avoiding(enemies)
+        .find()
 }
 
 pub fn distance_to_closest_pellet<F>(map: &game::Map, origin: &Position, enemies: &[Player], extra_filter: F) -> usize
     where F: Fn(&Position) -> bool {
     find_closest_pellet(map, origin, enemies, extra_filter)
         .map(|path| path.len())
-        .unwrap_or(usize::max_value())
+        .unwrap_or(usize::MAX)
+}
+
+// Every step costs one tick plus how dangerous the tile is, tiles more dangerous than the limit are not entered at all
+pub fn get_safest<F>(map: &game::Map, from: &Position, to: &Position, danger: &F, max_danger: u32) -> Option<Vec<Position>>
+    where F: DangerField {
+    PathQuery::new(map, from)
+        .to(to)
+        .extra_cost(|p| match danger.danger_at(p) {
+            Some(d) if d <= max_danger => Some(d as usize),
+            _ => None,
+        })
+        .find()
 }
 
-pub fn get_shortest(map: &game::Map, from: &Position, to: &Position) -> Option<Vec<Position>> {
-    let path = astar(from, |p| p.neighbours(map).into_iter().filter(|x| map.tile_at(x).is_walkable()).map(|x| (x, 1)), |p| p.manhattan_distance_to(&to, map) as usize, |p| *p == *to);
-    prepare_response(path)
+// Searches over both position and time, so that enemies are avoided where they are predicted to be when we
+// get there rather than where they are now. Walking in to a wall is how we wait, which shows up as the same
+// position twice in a row in the returned path.
+pub fn get_shortest_timed<P>(map: &game::Map, from: &Position, to: &Position, predictor: &P, max_ticks: usize) -> Option<Vec<Position>>
+    where P: OccupancyPredictor {
+    PathQuery::new(map, from)
+        .to(to)
+        // Don't walk in to where they'll be, nor pass them on the way there
+        .timed(max_ticks, |from, to, tick| {
+            let passing = predictor.is_occupied(to, tick) && predictor.is_occupied(from, tick + 1);
+            !passing && !predictor.is_occupied(to, tick + 1)
+        })
+        .find()
 }
 
-pub fn get_shortest_no_enemies(map: &game::Map, from: &Position, to: &Position, enemies: &[Player]) -> Option<Vec<Position>> {
-    let path = astar(from, |p| p.neighbours(map).into_iter().filter(|x| map.tile_at(x).is_walkable() && enemies.iter().find(|e| e.position() == *x).is_none()).map(|x| (x, 1)), |p| p.manhattan_distance_to(&to, map) as usize, |p| *p == *to);
-    prepare_response(path)
+// Direction to send for the first step of a path, walking in to a wall if the path starts by waiting
+pub fn first_direction(map: &game::Map, from: &Position, path: &[Position]) -> Option<Direction> {
+    match path.last() {
+        Some(next) if *next == *from => vec![Direction::Up, Direction::Down, Direction::Left, Direction::Right]
+            .into_iter()
+            .find(|d| !map.tile_at(&from.adjacent(map, d)).is_walkable()),
+        Some(next) => from.direction_to(map, next),
+        None => None,
+    }
 }
 
+// Paths from the pathfinding crate in the order we use, to compare against
+#[cfg(test)]
 fn prepare_response(path: Option<(Vec<Position>, usize)>) -> Option<Vec<Position>> {
     if let Some(x) = path {
         let mut sequence: Vec<Position> = x.0
@@ -129,6 +153,7 @@ fn prepare_response(path: Option<(Vec<Position>, usize)>) -> Option<Vec<Position
 #[cfg(test)]
 mod tests {
     use super::*;
+    use pathfinding::astar;
     use serde_json;
     use game::Map;
 
@@ -170,6 +195,55 @@ mod tests {
         assert_eq!(lib_path.as_slice(), bfs_path.as_slice());
     }
 
+    #[test]
+    fn safest_path_should_go_around_danger() {
+        struct Around(Position);
+        impl DangerField for Around {
+            fn danger_at(&self, position: &Position) -> Option<u32> {
+                if *position == self.0 { Some(10) } else { Some(0) }
+            }
+        }
+
+        let map: Map = serde_json::from_str(DEFAULT_MAP).unwrap();
+
+        let origin = Position::new(6, 5);
+        let destination = Position::new(6, 1);
+
+        let path = get_safest(&map, &origin, &destination, &Around(Position::new(26, 29)), 10).unwrap();
+        assert_eq!(4, path.len());
+
+        // Walking through the danger is cheaper than going around, unless it's above the limit
+        let path = get_safest(&map, &origin, &destination, &Around(Position::new(6, 3)), 10).unwrap();
+        assert_eq!(4, path.len());
+        let path = get_safest(&map, &origin, &destination, &Around(Position::new(6, 3)), 5).unwrap();
+        assert!(path.len() > 4);
+        assert!(!path.contains(&Position::new(6, 3)));
+    }
+
+    #[test]
+    fn timed_path_should_wait_for_enemy_to_pass() {
+        const LOOP: &'static str = r#"{"content":["|||||||","|_____|","|_|||_|","|_____|","|||||||"],"height":5,"pelletsleft":0,"width":7}"#;
+
+        struct PassingBy;
+        impl OccupancyPredictor for PassingBy {
+            fn is_occupied(&self, position: &Position, tick: usize) -> bool {
+                *position == Position::new(3, 1) && tick == 2
+            }
+        }
+
+        let map: Map = serde_json::from_str(LOOP).unwrap();
+
+        let origin = Position::new(1, 1);
+        let destination = Position::new(5, 1);
+
+        // Waiting one tick by walking in to the wall is faster than going around
+        let path = get_shortest_timed(&map, &origin, &destination, &PassingBy, 10).unwrap();
+        assert_eq!(5, path.len());
+        assert_eq!(origin, path[4]);
+        assert!(!path.contains(&Position::new(3, 3)));
+        assert_eq!(Some(Direction::Up), first_direction(&map, &origin, &path));
+    }
+
     fn get_cost_from_bfs_graph(map: &Map, from: &Position, to: &Position) -> usize {
         let mut graph = LocalPathGraph::new(&map);
         graph.update_from_map(&map, from);
@@ -193,6 +267,7 @@ mod benchmarks {
 
     use super::*;
     use self::test::Bencher;
+    use pathfinding::{astar, bfs};
     use serde_json;
     use game::{Map, MapInformation};
 
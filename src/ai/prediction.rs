@@ -0,0 +1,60 @@
+use common::Position;
+use game::DistanceTable;
+use protocol::Player;
+use traits::HasPosition;
+
+pub trait OccupancyPredictor {
+    // Whether an enemy is expected to be at the position `tick` ticks from now
+    fn is_occupied(&self, position: &Position, tick: usize) -> bool;
+}
+
+// Assumes enemies could be anywhere they are able to walk to, which is pessimistic but never wrong.
+// Predictions further than the horizon are too uncertain to be useful, so nothing is occupied after it.
+pub struct EnemyReachPredictor<'a> {
+    distances: &'a DistanceTable,
+    enemies: Vec<Position>,
+    horizon: usize,
+}
+
+impl<'a> EnemyReachPredictor<'a> {
+    pub fn new(distances: &'a DistanceTable, enemies: &[&Player], horizon: usize) -> EnemyReachPredictor<'a> {
+        EnemyReachPredictor {
+            distances,
+            enemies: enemies.iter().map(|e| e.position()).collect(),
+            horizon,
+        }
+    }
+}
+
+impl<'a> OccupancyPredictor for EnemyReachPredictor<'a> {
+    fn is_occupied(&self, position: &Position, tick: usize) -> bool {
+        if tick > self.horizon {
+            return false;
+        }
+
+        self.enemies
+            .iter()
+            .any(|e| self.distances.distance(e, position).map(|d| d <= tick).unwrap_or(false))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json;
+    use game::Map;
+
+    #[test]
+    fn enemies_can_reach_further_each_tick() {
+        const CORRIDOR: &str = r#"{"content":["|||||||","|_____|","|||||||"],"height":3,"pelletsleft":0,"width":7}"#;
+        let map: Map = serde_json::from_str(CORRIDOR).unwrap();
+        let distances = DistanceTable::from_map(&map);
+        let enemies: Vec<Player> = serde_json::from_str(r#"[{"id":1,"x":1,"y":1}]"#).unwrap();
+
+        let predictor = EnemyReachPredictor::new(&distances, &enemies.iter().collect::<Vec<&Player>>(), 2);
+        assert!(predictor.is_occupied(&Position::new(1, 1), 0));
+        assert!(!predictor.is_occupied(&Position::new(3, 1), 1));
+        assert!(predictor.is_occupied(&Position::new(3, 1), 2));
+        assert!(!predictor.is_occupied(&Position::new(4, 1), 3));
+    }
+}
@@ -0,0 +1,96 @@
+use rand::{Rng, XorShiftRng};
+
+use common::Direction;
+use game::{DistanceTable, SimState};
+use traits::HasDimensions;
+
+// How often chasers pick a random direction instead, nobody plays perfectly
+const CHASER_RANDOMNESS: f32 = 0.1;
+
+// Decides how a player moves during simulated ticks
+pub trait RolloutPolicy {
+    fn choose(&self, sim: &SimState, player: usize, distances: &DistanceTable, rng: &mut XorShiftRng) -> Direction;
+}
+
+pub fn from_name(name: &str) -> Option<Box<dyn RolloutPolicy>> {
+    match name {
+        "random" => Some(Box::new(RandomWalk)),
+        "greedy" => Some(Box::new(GreedyPellets)),
+        "chaser" => Some(Box::new(Chaser)),
+        _ => None,
+    }
+}
+
+// Legal moves, without turning around unless it's the only way to go
+fn forward_moves(sim: &SimState, player: usize) -> Vec<Direction> {
+    let moves = sim.legal_moves(player);
+    let forward: Vec<Direction> = match sim.players[player].last_direction {
+        Some(ref last) => moves.iter().filter(|d| !d.is_opposite_to(last)).cloned().collect(),
+        None => moves.clone(),
+    };
+
+    if forward.is_empty() { moves } else { forward }
+}
+
+fn pick(moves: &[Direction], rng: &mut XorShiftRng) -> Direction {
+    match rng.choose(moves) {
+        Some(d) => d.clone(),
+        None => Direction::Up,
+    }
+}
+
+pub struct RandomWalk;
+
+impl RolloutPolicy for RandomWalk {
+    fn choose(&self, sim: &SimState, player: usize, _: &DistanceTable, rng: &mut XorShiftRng) -> Direction {
+        pick(&forward_moves(sim, player), rng)
+    }
+}
+
+// Eats any pellet next to it, and wanders randomly otherwise
+pub struct GreedyPellets;
+
+impl RolloutPolicy for GreedyPellets {
+    fn choose(&self, sim: &SimState, player: usize, _: &DistanceTable, rng: &mut XorShiftRng) -> Direction {
+        let moves = forward_moves(sim, player);
+        let position = &sim.players[player].position;
+        let pellet_moves: Vec<Direction> = moves
+            .iter()
+            .filter(|d| sim.has_pellet(&position.adjacent(sim.map(), d)))
+            .cloned()
+            .collect();
+
+        if pellet_moves.is_empty() { pick(&moves, rng) } else { pick(&pellet_moves, rng) }
+    }
+}
+
+// Goes for us when we can be eaten, and runs away from us when we can eat it
+pub struct Chaser;
+
+impl RolloutPolicy for Chaser {
+    fn choose(&self, sim: &SimState, player: usize, distances: &DistanceTable, rng: &mut XorShiftRng) -> Direction {
+        let moves = forward_moves(sim, player);
+        let me = sim.me();
+        let chaser = &sim.players[player];
+        if player == 0 || !me.alive || rng.next_f32() < CHASER_RANDOMNESS {
+            return pick(&moves, rng);
+        }
+
+        let distance_to_me = |d: &Direction| {
+            let position = chaser.position.adjacent(distances, d);
+            distances.distance(&position, &me.position).unwrap_or(distances.width() as usize * distances.height() as usize)
+        };
+
+        let best = if me.is_dangerous() && !chaser.is_dangerous() {
+            moves.iter().max_by_key(|d| distance_to_me(d))
+        }
+        else {
+            moves.iter().min_by_key(|d| distance_to_me(d))
+        };
+
+        match best {
+            Some(d) => d.clone(),
+            None => pick(&moves, rng),
+        }
+    }
+}
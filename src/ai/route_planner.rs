@@ -0,0 +1,329 @@
+use std::cmp;
+use std::collections::HashMap;
+
+use common::Position;
+use game::{Map, MapInformation};
+
+// Discount per tick, makes pellets collected early worth a bit more than those collected late
+const DISCOUNT: f32 = 0.97;
+
+// Number of partial routes kept around while searching
+const BEAM_WIDTH: usize = 64;
+
+// A corridor between two turning points of the map, stored once per direction it can be walked in
+struct Corridor {
+    id: usize,
+    tiles: Vec<Position>, // Excluding the start, including the end node
+    end: usize,
+}
+
+// Plans tours of corridors that collect as many pellets as possible within a limited number of ticks,
+// rather than going for what looks best right now
+pub struct RoutePlanner {
+    nodes: Vec<Position>,
+    corridors: Vec<Corridor>,
+
+    // Outgoing corridors for each node
+    outgoing: Vec<Vec<usize>>,
+    node_indices: HashMap<Position, usize>,
+    // The corridors (in both directions) that a non-node tile is part of, and where along them it is
+    corridor_tiles: HashMap<Position, Vec<(usize, usize)>>,
+    corridor_count: usize,
+}
+
+// A partial route during the search
+#[derive(Clone)]
+struct Candidate {
+    corridors: Vec<usize>,
+    visited_corridors: Vec<bool>,
+    visited_nodes: Vec<bool>,
+
+    ticks: usize,
+    value: f32,
+    pellets: f32,
+    done: bool,
+}
+
+impl RoutePlanner {
+    pub fn from_map(map: &Map, map_information: &MapInformation) -> RoutePlanner {
+        // Everywhere that isn't a plain corridor tile is a node
+        let mut nodes: Vec<Position> = map_information
+            .walkable_positions()
+            .iter()
+            .filter(|p| p.neighbours(map).iter().filter(|n| map.tile_at(n).is_walkable()).count() != 2)
+            .cloned()
+            .collect();
+        nodes.sort_by_key(|p| (p.y, p.x));
+
+        let node_indices: HashMap<Position, usize> = nodes
+            .iter()
+            .enumerate()
+            .map(|(i, p)| (p.clone(), i))
+            .collect();
+
+        let mut planner = RoutePlanner {
+            outgoing: vec![Vec::new(); nodes.len()],
+            nodes,
+            corridors: Vec::new(),
+            node_indices,
+            corridor_tiles: HashMap::new(),
+            corridor_count: 0,
+        };
+
+        // Walk out from every node in each direction until another node is reached
+        let mut ids: HashMap<(usize, Position, usize, Position), usize> = HashMap::new();
+        for start in 0..planner.nodes.len() {
+            for first in planner.nodes[start].neighbours(map) {
+                if !map.tile_at(&first).is_walkable() {
+                    continue;
+                }
+
+                let mut previous = planner.nodes[start].clone();
+                let mut tiles = vec![first.clone()];
+                while !planner.node_indices.contains_key(tiles.last().unwrap()) {
+                    let current = tiles.last().unwrap().clone();
+                    let next = current.neighbours(map)
+                        .into_iter()
+                        .find(|n| map.tile_at(n).is_walkable() && *n != previous);
+
+                    match next {
+                        Some(n) => {
+                            previous = current;
+                            tiles.push(n);
+                        },
+                        None => break,
+                    }
+                }
+
+                let end = match planner.node_indices.get(tiles.last().unwrap()) {
+                    Some(&end) => end,
+                    None => continue,
+                };
+
+                // Both directions of a corridor share the same id
+                let last_inner = if tiles.len() > 1 { tiles[tiles.len() - 2].clone() } else { planner.nodes[start].clone() };
+                let key = (start, first.clone(), end, last_inner.clone());
+                let reverse_key = (end, last_inner, start, first);
+                let id = match ids.get(&reverse_key) {
+                    Some(&id) => id,
+                    None => {
+                        planner.corridor_count += 1;
+                        planner.corridor_count - 1
+                    },
+                };
+                ids.insert(key, id);
+
+                let index = planner.corridors.len();
+                for (offset, tile) in tiles[..tiles.len() - 1].iter().enumerate() {
+                    planner.corridor_tiles
+                        .entry(tile.clone())
+                        .or_default()
+                        .push((index, offset));
+                }
+
+                planner.outgoing[start].push(index);
+                planner.corridors.push(Corridor { id, tiles, end });
+            }
+        }
+
+        planner
+    }
+
+    // Best route from the position within the horizon, where each tile is worth what `pellet_value` says
+    // the first time it's walked on. Corridors where `is_blocked` is true for any tile are avoided.
+    // The path is returned in the same order as the pathfinding methods, the last position is the first step.
+    pub fn plan<F, B>(&self, from: &Position, horizon: usize, pellet_value: &F, is_blocked: B) -> Option<Vec<Position>>
+        where F: Fn(&Position) -> f32, B: Fn(&Position) -> bool {
+        let blocked: Vec<bool> = self.corridors
+            .iter()
+            .map(|c| c.tiles.iter().any(&is_blocked))
+            .collect();
+
+        let start = Candidate {
+            corridors: Vec::new(),
+            visited_corridors: vec![false; self.corridor_count],
+            visited_nodes: vec![false; self.nodes.len()],
+            ticks: 0,
+            value: 0.0,
+            pellets: 0.0,
+            done: false,
+        };
+
+        // Either at a node, or in the middle of a corridor where we start by walking to either end of it
+        let mut candidates = Vec::new();
+        let mut finished = Vec::new();
+        match self.node_indices.get(from) {
+            Some(&node) => {
+                let mut start = start;
+                start.visited_nodes[node] = true;
+                candidates = self.extend(&start, node, horizon, pellet_value, &blocked);
+            },
+            None => {
+                for &(corridor, offset) in self.corridor_tiles.get(from).map(|c| c.as_slice()).unwrap_or(&[]) {
+                    if !blocked[corridor] {
+                        candidates.push(self.walk(&start, corridor, offset + 1, horizon, pellet_value));
+                    }
+                }
+            },
+        }
+
+        // Beam search, one corridor at a time, only keeping the most promising routes around
+        loop {
+            let (done, active): (Vec<Candidate>, Vec<Candidate>) = candidates.into_iter().partition(|c| c.done || c.ticks == horizon);
+            finished.extend(done);
+            candidates = active;
+            if candidates.is_empty() {
+                break;
+            }
+
+            candidates.sort_by(|a, b| b.value.partial_cmp(&a.value).unwrap_or(cmp::Ordering::Equal).then(a.ticks.cmp(&b.ticks)));
+            candidates.truncate(BEAM_WIDTH);
+
+            candidates = candidates
+                .iter()
+                .flat_map(|c| self.extend(c, self.corridors[*c.corridors.last().unwrap()].end, horizon, pellet_value, &blocked))
+                .collect();
+        }
+
+        // Not worth following a route that doesn't pick up anything
+        let best = match finished.into_iter().max_by(|a, b| a.value.partial_cmp(&b.value).unwrap_or(cmp::Ordering::Equal)) {
+            Some(ref route) if route.pellets <= 0.0 => return None,
+            Some(route) => route,
+            None => return None,
+        };
+
+        let mut path = Vec::new();
+        for (i, &corridor) in best.corridors.iter().enumerate() {
+            // The first corridor might have been entered half way
+            let skip = match (i, self.corridor_tiles.get(from)) {
+                (0, Some(tiles)) => tiles.iter().find(|&&(c, _)| c == corridor).map(|&(_, o)| o + 1).unwrap_or(0),
+                _ => 0,
+            };
+            path.extend(self.corridors[corridor].tiles.iter().skip(skip).cloned());
+        }
+        path.truncate(horizon);
+        path.reverse();
+
+        Some(path)
+    }
+
+    // A new candidate for each corridor leading out of the node, or the finished route if there are none
+    fn extend<F>(&self, candidate: &Candidate, node: usize, horizon: usize, pellet_value: &F, blocked: &[bool]) -> Vec<Candidate>
+        where F: Fn(&Position) -> f32 {
+        let came_from = candidate.corridors.last().map(|&c| self.corridors[c].id);
+
+        let mut candidates = Vec::new();
+        for &corridor in &self.outgoing[node] {
+            // Turning around is only useful at the end of dead ends, where there is nothing else to do
+            let id = self.corridors[corridor].id;
+            if blocked[corridor] || (Some(id) == came_from && self.outgoing[node].len() > 1) {
+                continue;
+            }
+
+            candidates.push(self.walk(candidate, corridor, 0, horizon, pellet_value));
+        }
+
+        if candidates.is_empty() {
+            let mut finished = candidate.clone();
+            finished.done = true;
+            candidates.push(finished);
+        }
+
+        candidates
+    }
+
+    // Walks the corridor starting at the given tile offset, until the end of it or the horizon
+    fn walk<F>(&self, candidate: &Candidate, corridor: usize, offset: usize, horizon: usize, pellet_value: &F) -> Candidate
+        where F: Fn(&Position) -> f32 {
+        let (id, end, tiles) = (self.corridors[corridor].id, self.corridors[corridor].end, &self.corridors[corridor].tiles);
+
+        let mut next = candidate.clone();
+        for (i, tile) in tiles.iter().enumerate().skip(offset) {
+            if next.ticks == horizon {
+                break;
+            }
+            next.ticks += 1;
+
+            // Pellets are only collected the first time we pass
+            let is_end = i == tiles.len() - 1;
+            if (is_end && !candidate.visited_nodes[end]) || (!is_end && !candidate.visited_corridors[id]) {
+                let v = pellet_value(tile);
+                next.value += v * DISCOUNT.powi(next.ticks as i32);
+                next.pellets += v;
+            }
+        }
+
+        next.corridors.push(corridor);
+        next.visited_corridors[id] = true;
+        next.visited_nodes[end] = true;
+        next
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pathfinding;
+    use serde_json;
+    use std::collections::HashSet;
+
+    const DEFAULT_MAP: &str = r#"{"content":["||||||||||||||||||||||||||||","|............||............|","|.||||.|||||.||.|||||.||||.|","|o||||.|||||.||.|||||.||||.|","|.||||.|||||.||.|||||.||||.|","|....|................|....|","|.||||.||.||||||||.||.||||.|","|.||||.||.||||||||.||.||||.|","|....|.||....||....||.|....|","||||||.|||||_||_|||||.||||||","_____|.|||||_||_|||||.|_____","_____|.||__________||.|_____","_____|.||_|||--|||_||.|_____","||||||.||_|______|_||.||||||","______.___|______|___.______","||||||.||_|______|_||.||||||","_____|.||_|||--|||_||.|_____","_____|.||__________||.|_____","_____|.||_||||||||_||.|_____","||||||.||_||||||||_||.||||||","|....|.......||.......|....|","|.||||.|||||.||.|||||.||||.|","|.||||.|||||.||.|||||.||||.|","|o..||.......__.......||..o|","|||.||.||.||||||||.||.||.|||","|||.||.||.||||||||.||.||.|||","|......||....||....||......|","|.||||||||||.||.||||||||||.|","|.||||||||||.||.||||||||||.|","|..........................|","||||||||||||||||||||||||||||"],"height":31,"pelletsleft":238,"width":28}"#;
+
+    #[test]
+    fn can_plan_route_from_corridor() {
+        let map: Map = serde_json::from_str(DEFAULT_MAP).unwrap();
+        let planner = RoutePlanner::from_map(&map, &MapInformation::from_map(&map));
+
+        // Empty corridor at the spawn, pellets on both sides
+        let from = Position::new(13, 11);
+        let path = planner.plan(&from, 20, &|p: &Position| if map.tile_at(p).is_pellet() { 1.0 } else { 0.0 }, |_| false).unwrap();
+        assert_eq!(20, path.len());
+        assert!(path.iter().any(|p| map.tile_at(p).is_pellet()));
+        assert_eq!(1, from.manhattan_distance_to(path.last().unwrap(), &map));
+        for step in path.windows(2) {
+            assert_eq!(1, step[0].manhattan_distance_to(&step[1], &map));
+        }
+    }
+
+    #[test]
+    fn route_planning_should_beat_greedy_pellet_collection() {
+        let map: Map = serde_json::from_str(DEFAULT_MAP).unwrap();
+        let planner = RoutePlanner::from_map(&map, &MapInformation::from_map(&map));
+
+        const TICKS: usize = 250;
+        let start = Position::new(13, 11);
+
+        // Always walk towards the closest pellet
+        let closest = |position: &Position, pellets: &HashSet<Position>| {
+            pathfinding::bfs(position, |p| p.neighbours(&map).into_iter().filter(|x| map.tile_at(x).is_walkable()), |p| pellets.contains(p))
+                .map(|path| path[1].clone())
+        };
+        let greedy = simulate(&map, &start, TICKS, closest);
+
+        // Same fallback as when picking pellets, once there is nothing left within the horizon
+        let planned = simulate(&map, &start, TICKS, |position, pellets| {
+            planner.plan(position, 30, &|p: &Position| if pellets.contains(p) { 1.0 } else { 0.0 }, |_| false)
+                .map(|path| path.last().unwrap().clone())
+                .or_else(|| closest(position, pellets))
+        });
+
+        assert!(planned > greedy, "Pellets collected in {} ticks, greedy: {}, planned: {}", TICKS, greedy, planned);
+    }
+
+    fn simulate<F>(map: &Map, start: &Position, ticks: usize, next: F) -> usize
+        where F: Fn(&Position, &HashSet<Position>) -> Option<Position> {
+        let mut pellets = map.pellets();
+        let mut position = start.clone();
+        let total = pellets.len();
+
+        for _ in 0..ticks {
+            position = match next(&position, &pellets) {
+                Some(p) => p,
+                None => break,
+            };
+            pellets.remove(&position);
+        }
+
+        total - pellets.len()
+    }
+}
@@ -0,0 +1,223 @@
+use std::fmt;
+
+use ai::arbiter::{Policy, Proposal};
+use ai::is_threat;
+use common::{Direction, Position};
+use game::{DistanceTable, Map, MapInformation};
+use protocol::Player;
+use traits::HasPosition;
+
+const DIRECTIONS: [Direction; 4] = [Direction::Up, Direction::Down, Direction::Left, Direction::Right];
+
+// What's wrong with a move, from least to most likely to get us killed
+#[derive(Clone, Debug, PartialEq)]
+pub enum Hazard {
+    // Walking in to a wall leaves us standing still
+    Wall,
+    // One step away from where the enemy can be after this tick
+    NearEnemy(u32),
+    // In to a dead end that the enemy gets to the exit of before we're out
+    DeadEnd { exit: Position, enemy: u32 },
+    // Where the enemy can be after this tick
+    Enemy(u32),
+}
+
+impl Hazard {
+    fn severity(&self) -> usize {
+        match *self {
+            Hazard::Wall => 1,
+            Hazard::NearEnemy(_) => 2,
+            Hazard::DeadEnd { .. } => 3,
+            Hazard::Enemy(_) => 4,
+        }
+    }
+}
+
+impl fmt::Display for Hazard {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Hazard::Wall => write!(f, "walks in to a wall"),
+            Hazard::NearEnemy(id) => write!(f, "ends up next to where enemy {} can be", id),
+            Hazard::DeadEnd { ref exit, enemy } => write!(f, "enters a dead end that enemy {} can close off at {}", enemy, exit),
+            Hazard::Enemy(id) => write!(f, "ends up where enemy {} can be", id),
+        }
+    }
+}
+
+// Checks moves against the enemies that can eat us or get in our way, whatever strategy came up with them
+pub struct SafetyCheck<'a> {
+    map: &'a Map,
+    map_information: &'a MapInformation,
+    distances: &'a DistanceTable,
+    me: Position,
+    threats: Vec<&'a Player>,
+}
+
+impl<'a> SafetyCheck<'a> {
+    pub fn new(map: &'a Map, map_information: &'a MapInformation, distances: &'a DistanceTable, me: Position, enemies: &'a [Player], can_eat_others: bool) -> SafetyCheck<'a> {
+        SafetyCheck {
+            map,
+            map_information,
+            distances,
+            me,
+            threats: enemies.iter().filter(|e| is_threat(e, can_eat_others)).collect(),
+        }
+    }
+
+    // Closest threat to a position, and how far away it is
+    fn closest_threat(&self, position: &Position) -> Option<(usize, u32)> {
+        self.threats
+            .iter()
+            .filter_map(|e| self.distances.distance(&e.position(), position).map(|d| (d, e.id)))
+            .min()
+    }
+
+    // The worst thing about a move, if there's anything wrong with it. Walking in to a wall is only fine when the
+    // move is meant to make us wait.
+    pub fn hazard(&self, direction: &Direction, waits: bool) -> Option<Hazard> {
+        let next = self.me.adjacent(self.map, direction);
+        let walkable = self.map.tile_at(&next).is_walkable();
+        let next = if walkable { next } else { self.me.clone() };
+
+        match self.closest_threat(&next) {
+            Some((distance, id)) if distance <= 1 => return Some(Hazard::Enemy(id)),
+            _ => {},
+        }
+
+        if let Some(path) = self.map_information.path_to_dead_end_exit(&next) {
+            let exit = path[0].clone();
+            match self.closest_threat(&exit) {
+                Some((distance, enemy)) if distance <= path.len() + 1 => return Some(Hazard::DeadEnd { exit, enemy }),
+                _ => {},
+            }
+        }
+
+        match self.closest_threat(&next) {
+            Some((2, id)) => Some(Hazard::NearEnemy(id)),
+            _ if !walkable && !waits => Some(Hazard::Wall),
+            _ => None,
+        }
+    }
+
+    fn severity(&self, direction: &Direction, waits: bool) -> usize {
+        self.hazard(direction, waits).map(|h| h.severity()).unwrap_or(0)
+    }
+
+    // Distance to the closest threat after a move, further is better
+    fn margin(&self, direction: &Direction) -> usize {
+        let next = self.me.adjacent(self.map, direction);
+        let next = if self.map.tile_at(&next).is_walkable() { next } else { self.me.clone() };
+        self.closest_threat(&next).map(|(d, _)| d).unwrap_or(usize::MAX)
+    }
+}
+
+// A move that was turned down, and what we do instead
+pub struct Veto {
+    pub hazard: Hazard,
+    pub replacement: Direction,
+}
+
+// Turns down a move if any other move is safer. The replacement is what the policy picks from the proposals for
+// the safest moves, or the move that keeps us furthest from the threats if nobody proposed any of them.
+pub fn veto(check: &SafetyCheck, decision: &Direction, waits: bool, proposals: &[Proposal], policy: &dyn Policy) -> Option<Veto> {
+    let hazard = check.hazard(decision, waits)?;
+    let safest = DIRECTIONS.iter().map(|d| check.severity(d, false)).min().unwrap_or(0);
+    if hazard.severity() <= safest {
+        return None;
+    }
+
+    let safe: Vec<Proposal> = proposals
+        .iter()
+        .filter(|p| check.severity(&p.candidate.direction, p.candidate.rationale.waits) == safest)
+        .cloned()
+        .collect();
+
+    let replacement = match policy.choose(&safe) {
+        (Some(i), _) => safe[i].candidate.direction.clone(),
+        (None, _) => DIRECTIONS
+            .iter()
+            .filter(|d| check.severity(d, false) == safest)
+            .max_by_key(|d| check.margin(d))
+            .cloned()?,
+    };
+
+    Some(Veto { hazard, replacement })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ai::arbiter::PriorityPolicy;
+    use ai::strategies::{Candidate, StrategyType};
+    use protocol::{GameState, Message};
+    use std::str::FromStr;
+
+    // A loop with a short dead end hanging off the bottom at (4, 5), we're right above it
+    fn parse(enemy: (u32, u32)) -> GameState {
+        let message = format!(r#"{{"messagetype":"stateupdate","gamestate":{{"map":{{"content":["|||||||||","|_______|","|_|||||_|","|_|||||_|","|_______|","||||_||||","||||_||||","|||||||||"],"height":8,"width":9,"pelletsleft":0}},"you":{{"id":0,"x":4,"y":4}},"others":[{{"id":1,"x":{},"y":{},"isdangerous":true}}]}}}}"#, enemy.0, enemy.1);
+        match Message::from_str(&message).unwrap() {
+            Message::Update { state } => state,
+            _ => panic!(),
+        }
+    }
+
+    fn proposal(direction: Direction, score: f32) -> Proposal {
+        Proposal {
+            strategy: StrategyType::PickPellets,
+            candidate: Candidate::new(direction, score, String::new()),
+        }
+    }
+
+    #[test]
+    fn should_find_hazards() {
+        let state = parse((6, 4));
+        let map_information = MapInformation::from_map(&state.map);
+        let distances = DistanceTable::from_map(&state.map);
+        let check = SafetyCheck::new(&state.map, &map_information, &distances, state.me.position(), &state.enemies, false);
+
+        assert_eq!(Some(Hazard::NearEnemy(1)), check.hazard(&Direction::Up, false));
+        assert_eq!(Some(Hazard::DeadEnd { exit: Position::new(4, 4), enemy: 1 }), check.hazard(&Direction::Down, false));
+        assert_eq!(None, check.hazard(&Direction::Left, false));
+        assert_eq!(Some(Hazard::Enemy(1)), check.hazard(&Direction::Right, false));
+
+        let far = parse((1, 1));
+        let check = SafetyCheck::new(&far.map, &map_information, &distances, far.me.position(), &far.enemies, false);
+        assert_eq!(Some(Hazard::Wall), check.hazard(&Direction::Up, false));
+        assert_eq!(None, check.hazard(&Direction::Up, true));
+        assert_eq!(None, check.hazard(&Direction::Down, false));
+
+        // Another dangerous enemy still gets in our way while we're dangerous
+        let check = SafetyCheck::new(&state.map, &map_information, &distances, state.me.position(), &state.enemies, true);
+        assert_eq!(Some(Hazard::Enemy(1)), check.hazard(&Direction::Right, false));
+
+        // But the ones we can eat don't, though walking in to a wall by accident is still a mistake
+        let mut edible = parse((6, 4));
+        edible.enemies[0].is_dangerous = false;
+        let check = SafetyCheck::new(&edible.map, &map_information, &distances, edible.me.position(), &edible.enemies, true);
+        assert_eq!(Some(Hazard::Wall), check.hazard(&Direction::Up, false));
+        assert!(DIRECTIONS.iter().all(|d| check.hazard(d, true).is_none()));
+    }
+
+    #[test]
+    fn should_replace_unsafe_moves() {
+        let state = parse((6, 4));
+        let map_information = MapInformation::from_map(&state.map);
+        let distances = DistanceTable::from_map(&state.map);
+        let check = SafetyCheck::new(&state.map, &map_information, &distances, state.me.position(), &state.enemies, false);
+        let policy = PriorityPolicy::new(vec![StrategyType::PickPellets]);
+
+        let proposals = vec![proposal(Direction::Down, 1.0), proposal(Direction::Left, 0.5)];
+        let veto = veto(&check, &Direction::Down, false, &proposals, &policy).unwrap();
+        assert_eq!(Direction::Left, veto.replacement);
+
+        // Even when nobody wants to go there, and the fallback would walk in to a wall
+        assert_eq!(Direction::Left, super::veto(&check, &Direction::Up, false, &[], &policy).unwrap().replacement);
+        assert!(super::veto(&check, &Direction::Left, false, &proposals, &policy).is_none());
+
+        // Waiting on purpose is fine when nothing is after us
+        let far = parse((1, 1));
+        let check = SafetyCheck::new(&far.map, &map_information, &distances, far.me.position(), &far.enemies, false);
+        assert!(super::veto(&check, &Direction::Up, true, &proposals, &policy).is_none());
+        assert!(super::veto(&check, &Direction::Up, false, &proposals, &policy).is_some());
+    }
+}
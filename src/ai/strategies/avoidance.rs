@@ -1,25 +1,31 @@
 use std::collections::HashSet;
 
-use ai::{Bot, pathfinder};
-use ai::strategies::{Strategy, StrategyType};
+use ai::{Bot, is_threat, pathfinder};
+use ai::config::Params;
+use ai::strategies::{Candidate, Strategy, StrategyType};
 use common::Direction;
-use protocol::GameState;
+use protocol::{GameState, Player};
 use traits::HasPosition;
 
-pub struct Avoidance;
-
-impl Avoidance {
-    pub fn new() -> Avoidance {
-        Avoidance { }
-    }
+pub struct Avoidance {
+    // How close enemies that can eat us have to be before we start running
+    radius: usize,
+    // Number of ticks we want to be out of a dead end before anyone can block the exit
+    dead_end_margin: usize,
+    // Ticks to wait at the start of a round, before anyone has had a chance to spread out
+    warmup_ticks: u32,
 }
 
-impl Strategy for Avoidance {
-    fn description(&self) -> StrategyType {
-        StrategyType::Avoidance
+impl Avoidance {
+    pub fn from_params(params: &Params) -> Avoidance {
+        Avoidance {
+            radius: params.usize_or("radius", 3),
+            dead_end_margin: params.usize_or("dead_end_margin", 4),
+            warmup_ticks: params.usize_or("warmup_ticks", 5) as u32,
+        }
     }
 
-    fn action(&mut self, bot: &Bot, state: &GameState) -> Option<Direction> {
+    fn avoid(&self, bot: &Bot, state: &GameState) -> Option<Candidate> {
         let my_position = state.me.position();
 
         // Get out of dead ends if we might get blocked in
@@ -31,30 +37,43 @@ impl Strategy for Avoidance {
                 .iter()
                 // Ignore any enemies in dead ends, because
                 .filter(|e| !bot.map_information.is_dead_end(&e.position()))
-                .map(|e| pathfinder::get_shortest(&state.map, &e.position(), &dead_end_exit).unwrap())
-                .map(|path| path.len())
+                // Someone who can't get to the exit at all will never block it
+                .filter_map(|e| bot.distances.distance(&e.position(), &dead_end_exit))
                 .max()
                 .unwrap_or(usize::max_value());
 
-            if path_to_exit.len() + 4 > closest_enemy_distance_to_exit {
+            if path_to_exit.len() + self.dead_end_margin > closest_enemy_distance_to_exit {
                 // Time to get out
-                // println!("GETTING OUT OF DEAD END: My distance to exit is {} and the closest enemy can reach that in {} steps"
-                //     , path_to_exit.len()
-                //     , closest_enemy_distance_to_exit);
-                return state.me.position().direction_to(&state.map, &path_to_exit.last().unwrap());
+                let reason = format!("Getting out of the dead end, the exit is {} steps away and an enemy can get there in {}", path_to_exit.len(), closest_enemy_distance_to_exit);
+                return state.me.position().direction_to(&state.map, path_to_exit.last().unwrap()).map(|d| {
+                    Candidate::new(d, 1.0, reason)
+                        .with_target(dead_end_exit)
+                        .with_score("steps_to_exit", path_to_exit.len() as f32)
+                        .with_score("enemy_steps_to_exit", closest_enemy_distance_to_exit as f32)
+                        .with_path(path_to_exit)
+                });
             }
         }
 
-        if bot.tick < 5 {
+        if bot.tick < self.warmup_ticks {
             return None;
         }
 
-        let directions_to_avoid = state.enemies
+        // Nothing that can hurt us is close enough to worry about
+        if bot.threat_distances.distance_at(&my_position).map(|d| d > self.radius).unwrap_or(true) {
+            return None;
+        }
+
+        let nearby: Vec<&Player> = state.enemies
+            .iter()
+            .filter(|e| is_threat(e, bot.can_eat_others()))
+            .filter(|e| bot.path_graph.cost_to(&e.position()).map(|c| c <= self.radius).unwrap_or(false))
+            .collect();
+        let threats: Vec<u32> = nearby.iter().map(|e| e.id).collect();
+
+        let directions_to_avoid = nearby
             .iter()
-            .filter(|e| !(bot.can_eat_others() && !e.is_dangerous))
-            .map(|e| (bot.path_graph.cost_to(&e.position()).unwrap_or(usize::max_value()), e))
-            .filter(|&(c, _)| c <= 3)
-            .map(|(_, e)| bot.path_graph.path_to(&e.position()).unwrap().last().unwrap().clone())
+            .map(|e| bot.path_graph.path_to(&e.position()).unwrap().last().unwrap().clone())
             .map(|pos| state.me.position().direction_to(&state.map, &pos))
             .collect::<Option<HashSet<Direction>>>();
 
@@ -77,13 +96,10 @@ impl Strategy for Avoidance {
                     let dtp = pathfinder::distance_to_closest_pellet(&state.map, &p, &state.enemies, |p| !bot.map_information.is_dead_end(p));
                     (dtp, d)
                 })
-                .min_by(|&(d1, _), &(d2, _)| d1.cmp(&d2))
-                .map(|(_, d)| d) {
-                return Some(optimal_direction.clone());
-            }
-
-            if possible_directions.len() > 0 {
-                return Some(possible_directions[0].clone());
+                .min_by(|&(d1, _), &(d2, _)| d1.cmp(&d2)) {
+                return Some(Candidate::new(optimal_direction.1.clone(), 1.0, format!("Running from enemies {:?}, towards a pellet {} steps away", threats, optimal_direction.0))
+                    .with_score("pellet_distance", optimal_direction.0 as f32)
+                    .with_threats(threats));
             }
         }
 
@@ -91,6 +107,20 @@ impl Strategy for Avoidance {
     }
 }
 
+impl Strategy for Avoidance {
+    fn description(&self) -> StrategyType {
+        StrategyType::Avoidance
+    }
+
+    fn action(&mut self, bot: &Bot, state: &GameState) -> Option<Direction> {
+        self.avoid(bot, state).map(|c| c.direction)
+    }
+
+    fn candidates(&mut self, bot: &Bot, state: &GameState) -> Vec<Candidate> {
+        self.avoid(bot, state).into_iter().collect()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -108,8 +138,9 @@ mod tests {
                 let mut bot = Bot::from_game_state(&state);
                 bot.tick = 5; // Required for avoidance
                 bot.path_graph.update_from_map(&state.map, &state.me.position());
+                bot.update_distance_fields(&state);
 
-                let mut strategy = Avoidance::new();
+                let mut strategy = Avoidance::from_params(&Params::default());
                 let direction = strategy.action(&bot, &state);
                 assert_eq!(Direction::Up, direction.unwrap());
             }
@@ -0,0 +1,183 @@
+use ai::config::Params;
+use ai::strategies::{Candidate, StrategyType};
+use ai::{Bot, Strategy};
+use common::{Direction, Position};
+use game::MapInformation;
+use protocol::GameState;
+use traits::HasPosition;
+
+const DIRECTIONS: [Direction; 4] = [Direction::Up, Direction::Down, Direction::Left, Direction::Right];
+
+// Instead of chasing enemies in to dead ends, where they can slip past us if the area has loops, we take the
+// exit and wait there. Once we're sure to catch them before we stop being dangerous, we close in.
+pub struct Cornering {
+    // Extra ticks of danger we want left when we expect to have caught the enemy
+    margin: usize,
+}
+
+impl Cornering {
+    pub fn from_params(params: &Params) -> Cornering {
+        Cornering {
+            margin: params.usize_or("margin", 2),
+        }
+    }
+
+    // Steps from the exit to the deepest tile behind it, the furthest an enemy in there can run
+    fn area_depth(map_information: &MapInformation, exit: &Position) -> usize {
+        map_information
+            .dead_ends()
+            .values()
+            .filter(|d| d.exit == *exit)
+            .map(|d| d.depth)
+            .max()
+            .unwrap_or(0)
+    }
+
+    // Standing still is done by walking in to a wall, if there's no wall we step back out of the dead end
+    fn hold(bot: &Bot, state: &GameState, exit: &Position) -> Option<Direction> {
+        let me = state.me.position();
+        DIRECTIONS
+            .iter()
+            .find(|d| !state.map.tile_at(&me.adjacent(&state.map, d)).is_walkable())
+            .or_else(|| DIRECTIONS.iter().find(|d| {
+                let next = me.adjacent(&state.map, d);
+                state.map.tile_at(&next).is_walkable() && bot.map_information.dead_end(&next).map(|e| e.exit != *exit).unwrap_or(true)
+            }))
+            .cloned()
+    }
+
+    fn corner(&self, bot: &Bot, state: &GameState) -> Option<Candidate> {
+        if !bot.can_eat_others() {
+            return None;
+        }
+
+        let me = state.me.position();
+        let my_ticks = bot.remaining_ticks_dangerous as usize;
+
+        let (enemy, exit, to_exit) = state.enemies
+            .iter()
+            .filter_map(|e| bot.map_information.dead_end(&e.position()).map(|d| (e, d.exit.clone())))
+            // We're already in there with them, that's up to the hunter
+            .filter(|(_, exit)| bot.map_information.dead_end(&me).map(|d| d.exit != *exit).unwrap_or(true))
+            .filter_map(|(e, exit)| {
+                let escape = bot.map_information.path_to_dead_end_exit(&e.position())?.len();
+                let to_exit = bot.distances.distance(&me, &exit)?;
+
+                // They get out before we get there
+                if to_exit > escape {
+                    return None;
+                }
+
+                Some((e, exit, to_exit))
+            })
+            .min_by_key(|&(_, _, to_exit)| to_exit)?;
+
+        let depth = Cornering::area_depth(&bot.map_information, &exit);
+        let enemy_ticks = bot.opponents.remaining_dangerous_ticks(enemy.id) as usize;
+
+        // Not worth waiting if they stay dangerous for longer than we do
+        if enemy_ticks.max(to_exit) + depth + self.margin > my_ticks {
+            return None;
+        }
+
+        let candidate = |direction: Direction, reason: String, target: Position| {
+            Candidate::new(direction, 1.0, reason)
+                .with_path(bot.distances.path(&me, &target).unwrap_or_default())
+                .with_target(target)
+                .with_score("to_exit", to_exit as f32)
+                .with_score("depth", depth as f32)
+                .with_score("enemy_ticks", enemy_ticks as f32)
+                .with_score("my_ticks", my_ticks as f32)
+        };
+
+        if enemy_ticks == 0 && to_exit + depth + self.margin <= my_ticks {
+            return bot.distances
+                .next_hop(&me, &enemy.position())
+                .map(|d| candidate(d, format!("Closing in on enemy {}, trapped behind {}", enemy.id, exit), enemy.position()));
+        }
+
+        let direction = if to_exit == 0 {
+            Cornering::hold(bot, state, &exit)
+        }
+        else {
+            bot.distances.next_hop(&me, &exit)
+        };
+
+        direction.map(|d| {
+            // Holding still is walking in to a wall, which the safety check has to let through
+            let waits = !state.map.tile_at(&me.adjacent(&state.map, &d)).is_walkable();
+            let held = candidate(d, format!("Holding the exit at {} until enemy {} can be eaten", exit, enemy.id), exit.clone());
+            if waits { held.waiting() } else { held }
+        })
+    }
+}
+
+impl Strategy for Cornering {
+    fn description(&self) -> StrategyType {
+        StrategyType::Cornering
+    }
+
+    fn action(&mut self, bot: &Bot, state: &GameState) -> Option<Direction> {
+        self.corner(bot, state).map(|c| c.direction)
+    }
+
+    fn candidates(&mut self, bot: &Bot, state: &GameState) -> Vec<Candidate> {
+        self.corner(bot, state).into_iter().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use protocol::Message;
+    use std::str::FromStr;
+
+    // A loop behind a single exit at (8, 2), with an enemy inside it
+    fn parse(me: (u32, u32), enemy_dangerous: bool) -> GameState {
+        let message = format!(r#"{{"gamestate":{{"map":{{"content":["|||||||||||||||||","|_______________|","||||||||_||||||||","|||||||___|||||||","|||||||_|_|||||||","|||||||___|||||||","|||||||||||||||||"],"height":7,"pelletsleft":0,"width":17}},"others":[{{"id":1,"isdangerous":{},"score":0,"x":8,"y":5}}],"you":{{"id":0,"isdangerous":true,"score":0,"x":{},"y":{}}}}},"messagetype":"stateupdate"}}"#, enemy_dangerous, me.0, me.1);
+        match Message::from_str(&message).unwrap() {
+            Message::Update { state } => state,
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn should_wait_at_exit_until_enemy_can_be_eaten() {
+        let state = parse((5, 1), true);
+        let mut bot = Bot::from_game_state(&state);
+        bot.remaining_ticks_dangerous = 200;
+        bot.opponents.track_danger(&state.enemies);
+
+        // The enemy is still dangerous, so we only head for the exit
+        let mut cornering = Cornering::from_params(&Params::default());
+        assert_eq!(Some(Direction::Right), cornering.action(&bot, &state));
+
+        // Standing on the exit, we walk in to a wall rather than going in after it
+        let state = parse((8, 2), true);
+        let held = cornering.action(&bot, &state).unwrap();
+        assert!(!state.map.tile_at(&Position::new(8, 2).adjacent(&state.map, &held)).is_walkable());
+
+        // Once it can be eaten we go in
+        let state = parse((8, 2), false);
+        bot.opponents.track_danger(&state.enemies);
+        assert_eq!(Some(Direction::Down), cornering.action(&bot, &state));
+    }
+
+    #[test]
+    fn should_not_be_vetoed_off_the_exit() {
+        let state = parse((8, 2), true);
+        let mut bot = Bot::from_game_state(&state);
+        bot.remaining_ticks_dangerous = 200;
+
+        // Holding still walks in to a wall, which the safety check would otherwise turn down
+        for _ in 0..3 {
+            let direction = bot.determine_action(parse((8, 2), true));
+            assert_eq!(Position::new(8, 2), walk(&parse((8, 2), true), &direction), "went {}", direction);
+        }
+    }
+
+    fn walk(state: &GameState, direction: &Direction) -> Position {
+        let next = state.me.position().adjacent(&state.map, direction);
+        if state.map.tile_at(&next).is_walkable() { next } else { state.me.position() }
+    }
+}
@@ -0,0 +1,258 @@
+use ai::config::Params;
+use ai::interception;
+use ai::strategies::{Candidate, StrategyType};
+use ai::territory::Owner;
+use ai::{Bot, Strategy};
+use common::{Direction, Position, rules};
+use protocol::{GameState, Player};
+use traits::HasPosition;
+
+// Chance of catching someone after taking a super pellet, a guess
+const CATCH_CHANCE: f32 = 0.5;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Play {
+    // Eat the pellets we get to first
+    Race,
+    // Take the pellets the leader is going for, which is worth both what we get and what they don't
+    Deny,
+    // Go for kills, or the super pellet that lets us make them
+    Hunt,
+}
+
+struct Plan {
+    play: Play,
+    direction: Direction,
+    target: Position,
+    points: f32,
+    ticks: usize,
+}
+
+impl Plan {
+    // Points per tick, there are not many ticks left
+    fn value(&self) -> f32 {
+        self.points / (self.ticks + 1) as f32
+    }
+}
+
+// With few pellets left the nearest pellet is not always the best one, so the ways we can still get points are
+// weighed against each other, and against how far ahead or behind we are
+pub struct Endgame {
+    // Pellets left when the endgame starts
+    pellets_left: usize,
+    scores: rules::Scores,
+}
+
+impl Endgame {
+    pub fn from_params(params: &Params) -> Endgame {
+        Endgame {
+            pellets_left: params.usize_or("pellets_left", 20),
+            scores: params.scores(),
+        }
+    }
+
+    fn pellet_score(&self, state: &GameState, position: &Position) -> f32 {
+        if state.map.tile_at(position).is_super_pellet() { self.scores.super_pellet as f32 } else { self.scores.pellet as f32 }
+    }
+
+    fn plan(bot: &Bot, state: &GameState, play: Play, target: Position, points: f32) -> Option<Plan> {
+        let me = state.me.position();
+        Some(Plan {
+            play,
+            direction: bot.distances.next_hop(&me, &target)?,
+            ticks: bot.distances.distance(&me, &target)?,
+            target,
+            points,
+        })
+    }
+
+    fn race(&self, bot: &Bot, state: &GameState, pellets: &[Position]) -> Option<Plan> {
+        let mut points = 0.0;
+        for pellet in pellets {
+            points += match bot.territory.owner_of(pellet) {
+                Some(Owner::Me) => self.pellet_score(state, pellet),
+                Some(Owner::Tie) => 0.5 * self.pellet_score(state, pellet),
+                _ => 0.0,
+            };
+        }
+
+        let nearest = pellets
+            .iter()
+            .filter(|p| bot.territory.owner_of(p).map(|o| o != Owner::Me && o != Owner::Tie) != Some(true))
+            .filter_map(|p| bot.distances.distance(&state.me.position(), p).map(|d| (d, p)))
+            .min_by_key(|&(d, _)| d)?;
+
+        Endgame::plan(bot, state, Play::Race, nearest.1.clone(), points)
+    }
+
+    fn deny(&self, bot: &Bot, state: &GameState, leader: &Player) -> Option<Plan> {
+        // Only the pellets they're going for that we can get to as soon as they do
+        bot.intents
+            .targets(leader.id)
+            .iter()
+            .filter(|&(p, _)| bot.territory.owner_of(p).map(|o| o == Owner::Me || o == Owner::Tie) == Some(true))
+            .filter_map(|&(ref p, probability)| Endgame::plan(bot, state, Play::Deny, p.clone(), 2.0 * probability * self.pellet_score(state, p)))
+            .max_by(|a, b| a.value().partial_cmp(&b.value()).unwrap())
+    }
+
+    fn hunt(&self, bot: &Bot, state: &GameState, ticks_left: usize) -> Option<Plan> {
+        let me = state.me.position();
+        if bot.can_eat_others() {
+            return state.enemies
+                .iter()
+                .filter(|e| bot.opponents.remaining_dangerous_ticks(e.id) == 0)
+                .filter_map(|e| interception::intercept(&state.map, bot.map_information.walkable_positions(), &bot.distances, &me, &e.position()).map(|i| (e, i)))
+                .filter(|(_, i)| i.ticks_to_catch < (bot.remaining_ticks_dangerous as usize).min(ticks_left))
+                .min_by_key(|(_, i)| i.ticks_to_catch)
+                .map(|(e, i)| Plan {
+                    play: Play::Hunt,
+                    direction: i.direction,
+                    target: e.position(),
+                    points: self.scores.eat_player as f32,
+                    ticks: i.ticks_to_catch,
+                });
+        }
+
+        if state.enemies.is_empty() {
+            return None;
+        }
+
+        let mut super_pellets: Vec<Position> = state.map.super_pellets().into_iter().filter(|p| bot.territory.is_ours(p)).collect();
+        super_pellets.sort_by_key(|p| (p.y, p.x));
+        super_pellets
+            .into_iter()
+            .filter_map(|p| Endgame::plan(bot, state, Play::Hunt, p, self.scores.super_pellet as f32 + CATCH_CHANCE * self.scores.eat_player as f32))
+            .filter(|plan| plan.ticks < ticks_left)
+            .min_by_key(|plan| plan.ticks)
+    }
+
+    fn plans(&self, bot: &Bot, state: &GameState) -> Vec<Plan> {
+        let mut pellets: Vec<Position> = state.map.pellets().into_iter().collect();
+        if pellets.is_empty() || pellets.len() > self.pellets_left {
+            return Vec::new();
+        }
+        pellets.sort_by_key(|p| (p.y, p.x));
+
+        // The game is over once the last pellet is eaten, which takes at least this long if we leave them to the enemies
+        let ticks_left = pellets
+            .iter()
+            .filter_map(|p| state.enemies.iter().filter_map(|e| bot.distances.distance(&e.position(), p)).min())
+            .max()
+            .unwrap_or(0);
+        let available: f32 = pellets.iter().map(|p| self.pellet_score(state, p)).sum();
+        let leader = state.enemies.iter().max_by_key(|e| e.score);
+        let lead = state.me.score as f32 - leader.map(|e| e.score as f32).unwrap_or(0.0);
+
+        let mut plans: Vec<Plan> = vec![
+            self.race(bot, state, &pellets),
+            leader.and_then(|l| self.deny(bot, state, l)),
+            self.hunt(bot, state, ticks_left),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+
+        // Too far behind for pellets to make a difference, or so far ahead that there's no need to take risks
+        let wanted: fn(&Plan) -> bool = if -lead > available {
+            |p| p.play == Play::Hunt
+        }
+        else if lead > available {
+            |p| p.play != Play::Hunt
+        }
+        else {
+            |_| true
+        };
+        if plans.iter().any(wanted) {
+            plans.retain(wanted);
+        }
+
+        plans.sort_by(|a, b| b.value().partial_cmp(&a.value()).unwrap());
+        plans
+    }
+}
+
+impl Strategy for Endgame {
+    fn description(&self) -> StrategyType {
+        StrategyType::Endgame
+    }
+
+    fn action(&mut self, bot: &Bot, state: &GameState) -> Option<Direction> {
+        self.plans(bot, state).into_iter().next().map(|p| p.direction)
+    }
+
+    fn candidates(&mut self, bot: &Bot, state: &GameState) -> Vec<Candidate> {
+        let plans = self.plans(bot, state);
+        let best = plans.first().map(|p| p.value()).unwrap_or(0.0);
+        plans
+            .into_iter()
+            .map(|p| {
+                let reason = format!("{:?} for {:.1} points in {} ticks, towards {}", p.play, p.points, p.ticks, p.target);
+                let score = if best > 0.0 { p.value() / best } else { 0.0 };
+                Candidate::new(p.direction, score, reason)
+                    .with_score("points", p.points)
+                    .with_score("ticks", p.ticks as f32)
+                    .with_target(p.target)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use protocol::Message;
+    use serde_json::Value;
+    use std::str::FromStr;
+
+    // Two pellets close to us on the left, and a super pellet we get to before the enemy on the right
+    fn parse(my_score: u32, enemy_score: u32) -> GameState {
+        let message = format!(r#"{{"gamestate":{{"map":{{"content":["|||||||||||||||","|.._____o_____|","|||||||||||||||"],"height":3,"pelletsleft":3,"width":15}},"others":[{{"id":1,"isdangerous":false,"score":{},"x":13,"y":1}}],"you":{{"id":0,"isdangerous":false,"score":{},"x":4,"y":1}}}},"messagetype":"stateupdate"}}"#, enemy_score, my_score);
+        match Message::from_str(&message).unwrap() {
+            Message::Update { state } => state,
+            _ => panic!(),
+        }
+    }
+
+    fn plans(state: &GameState, remaining_ticks_dangerous: u32) -> Vec<Plan> {
+        let mut bot = Bot::from_game_state(state);
+        bot.remaining_ticks_dangerous = remaining_ticks_dangerous;
+        bot.territory.update_from_map(&state.map, &state.me.position(), &state.enemies);
+        bot.intents.update(&state.map, &bot.distances, &state.enemies, &bot.opponents);
+        Endgame::from_params(&Params::default()).plans(&bot, state)
+    }
+
+    fn plays(state: &GameState) -> Vec<Play> {
+        plans(state, 0).into_iter().map(|p| p.play).collect()
+    }
+
+    #[test]
+    fn should_weigh_plays_against_the_standings() {
+        // Close game, both the pellets on the left and the super pellet on the right are worth going for
+        let close = plans(&parse(10, 10), 0);
+        let race = close.iter().find(|p| p.play == Play::Race).unwrap();
+        assert_eq!((Direction::Left, Position::new(2, 1), 2), (race.direction.clone(), race.target.clone(), race.ticks));
+        let hunt = close.iter().find(|p| p.play == Play::Hunt).unwrap();
+        assert_eq!((Direction::Right, Position::new(8, 1), 4), (hunt.direction.clone(), hunt.target.clone(), hunt.ticks));
+        let scores = rules::ESTIMATED_SCORES;
+        assert_eq!(scores.super_pellet as f32 + CATCH_CHANCE * scores.eat_player as f32, hunt.points);
+
+        // Once we can eat them, the hunt is for the enemy itself
+        let dangerous = plans(&parse(10, 10), 50);
+        let hunt = dangerous.iter().find(|p| p.play == Play::Hunt).unwrap();
+        assert_eq!((Direction::Right, Position::new(13, 1)), (hunt.direction.clone(), hunt.target.clone()));
+        assert_eq!(scores.eat_player as f32, hunt.points);
+
+        // The pellets can't win it for us anymore
+        assert_eq!(vec![Play::Hunt], plays(&parse(10, 30)));
+
+        // Nothing to gain from taking risks
+        assert!(!plays(&parse(30, 10)).contains(&Play::Hunt));
+
+        // Not the endgame yet
+        let mut params = Params::default();
+        params.set("pellets_left", Value::from(2));
+        let mut endgame = Endgame::from_params(&params);
+        let state = parse(10, 10);
+        assert_eq!(None, endgame.action(&Bot::from_game_state(&state), &state));
+    }
+}
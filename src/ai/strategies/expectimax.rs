@@ -0,0 +1,410 @@
+use std::cell::Cell;
+use std::f32;
+use std::time::{Duration, Instant};
+
+use ai::config::Params;
+use ai::opponents::Context;
+use ai::strategies::{Candidate, StrategyType};
+use ai::{Bot, Strategy};
+use common::{Direction, Position, rules};
+use game::{DistanceTable, MapInformation, SimState};
+use protocol::GameState;
+use traits::HasPosition;
+
+// Weights of the evaluation, being eaten outweighs everything else
+const DEATH_VALUE: f32 = -1000.0;
+const SCORE_WEIGHT: f32 = 1.0;
+const SAFETY_WEIGHT: f32 = 2.0;
+const TERRITORY_WEIGHT: f32 = 0.1;
+
+// Being further away than this from enemies is considered completely safe
+const SAFE_DISTANCE: usize = 6;
+
+// Looking at the clock for every node would take longer than the nodes themselves
+const NODES_PER_CLOCK_CHECK: usize = 64;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OpponentModel {
+    // Enemies pick any of their moves with equal probability
+    Expectimax,
+    // Enemies pick the move that is worst for us
+    Paranoid,
+}
+
+// Depth-limited search over our moves and those of the closest enemies. Players only get to choose at turning
+// points, in corridors they keep going the way they were going, which keeps the tree small.
+pub struct Lookahead<'a> {
+    map_information: &'a MapInformation,
+    distances: &'a DistanceTable,
+    model: OpponentModel,
+
+    // Indices of the enemies that are searched over, the others just keep going
+    opponents: Vec<usize>,
+    horizon: usize,
+
+    // Shared equally between our moves, so that the last one gets looked at as closely as the first
+    max_nodes: usize,
+    time_budget: Option<Duration>,
+    nodes: Cell<usize>,
+    node_limit: Cell<usize>,
+    deadline: Cell<Option<Instant>>,
+
+    // Relative likelihood of each of the choices an enemy has, equally likely if not given
+    move_weights: Option<MoveWeights<'a>>,
+}
+
+type MoveWeights<'a> = Box<dyn Fn(&SimState, usize, &[Direction]) -> Vec<f32> + 'a>;
+
+impl<'a> Lookahead<'a> {
+    pub fn new(map_information: &'a MapInformation, distances: &'a DistanceTable, model: OpponentModel, opponents: Vec<usize>, horizon: usize, max_nodes: usize) -> Lookahead<'a> {
+        Lookahead {
+            map_information,
+            distances,
+            model,
+            opponents,
+            horizon,
+            max_nodes,
+            time_budget: None,
+            nodes: Cell::new(0),
+            node_limit: Cell::new(max_nodes),
+            deadline: Cell::new(None),
+            move_weights: None,
+        }
+    }
+
+    pub fn with_time_budget(mut self, budget: Duration) -> Lookahead<'a> {
+        self.time_budget = Some(budget);
+        self
+    }
+
+    pub fn with_move_weights<F>(mut self, weights: F) -> Lookahead<'a>
+        where F: Fn(&SimState, usize, &[Direction]) -> Vec<f32> + 'a {
+        self.move_weights = Some(Box::new(weights));
+        self
+    }
+
+    // The value of each of our legal moves right now
+    pub fn evaluate_moves(&self, root: &SimState) -> Vec<(Direction, f32)> {
+        let start = Instant::now();
+        let root_score = root.me().score;
+        let mut sim = root.clone();
+        let moves = root.legal_moves(0);
+        let count = moves.len() as u32;
+
+        moves
+            .into_iter()
+            .enumerate()
+            .map(|(i, d)| {
+                self.nodes.set(0);
+                self.node_limit.set(self.max_nodes / count as usize);
+                self.deadline.set(self.time_budget.map(|budget| start + budget * (i as u32 + 1) / count));
+
+                let value = self.expected_value(&mut sim, &d, root_score, 0);
+                (d, value)
+            })
+            .collect()
+    }
+
+    // Whether this move has used up its share of nodes or time
+    fn out_of_budget(&self) -> bool {
+        let nodes = self.nodes.get();
+        if nodes >= self.node_limit.get() {
+            return true;
+        }
+
+        match self.deadline.get() {
+            Some(deadline) if nodes.is_multiple_of(NODES_PER_CLOCK_CHECK) && Instant::now() >= deadline => {
+                self.node_limit.set(nodes);
+                true
+            },
+            _ => false,
+        }
+    }
+
+    fn value(&self, sim: &mut SimState, root_score: u32, depth: usize) -> f32 {
+        if !sim.me().alive {
+            return DEATH_VALUE;
+        }
+
+        self.nodes.set(self.nodes.get() + 1);
+        if depth >= self.horizon || self.out_of_budget() {
+            return self.evaluate(sim, root_score);
+        }
+
+        self.choices(sim, 0)
+            .iter()
+            .map(|d| self.expected_value(sim, d, root_score, depth))
+            .fold(f32::MIN, f32::max)
+    }
+
+    // Value of us making a move, given what the enemies might do at the same time
+    fn expected_value(&self, sim: &mut SimState, direction: &Direction, root_score: u32, depth: usize) -> f32 {
+        // Every combination of enemy moves, together with how likely it is
+        let mut joint_moves: Vec<(Vec<Direction>, f32)> = vec![(vec![direction.clone()], 1.0)];
+        for player in 1..sim.players.len() {
+            let choices: Vec<(Direction, f32)> = if self.opponents.contains(&player) {
+                self.weighted_choices(sim, player)
+            }
+            else {
+                self.choices(sim, player).into_iter().take(1).map(|d| (d, 1.0)).collect()
+            };
+
+            joint_moves = joint_moves
+                .into_iter()
+                .flat_map(|(moves, probability)| choices.iter().map(move |&(ref d, p)| {
+                    let mut moves = moves.clone();
+                    moves.push(d.clone());
+                    (moves, probability * p)
+                }))
+                .collect();
+        }
+
+        let values = joint_moves.iter().map(|&(ref moves, probability)| {
+            sim.apply(moves);
+            let value = self.value(sim, root_score, depth + 1);
+            sim.undo();
+            (value, probability)
+        });
+
+        match self.model {
+            OpponentModel::Expectimax => values.map(|(v, p)| v * p).sum::<f32>(),
+            OpponentModel::Paranoid => values.map(|(v, _)| v).fold(f32::MAX, f32::min),
+        }
+    }
+
+    // The choices of an enemy and their probabilities
+    fn weighted_choices(&self, sim: &SimState, player: usize) -> Vec<(Direction, f32)> {
+        let choices = self.choices(sim, player);
+        let weights = match self.move_weights {
+            Some(ref move_weights) => move_weights(sim, player, &choices),
+            None => vec![1.0; choices.len()],
+        };
+
+        let total: f32 = weights.iter().sum();
+        if total <= 0.0 {
+            let count = choices.len() as f32;
+            return choices.into_iter().map(|d| (d, 1.0 / count)).collect();
+        }
+
+        choices.into_iter().zip(weights).map(|(d, w)| (d, w / total)).collect()
+    }
+
+    // Every legal move at turning points, or when we don't know where the player is heading, otherwise keep going
+    fn choices(&self, sim: &SimState, player: usize) -> Vec<Direction> {
+        let moves = sim.legal_moves(player);
+        let player = &sim.players[player];
+        if !player.alive {
+            return vec![Direction::Up];
+        }
+
+        match player.last_direction {
+            Some(ref last) if !self.map_information.is_turning_point(&player.position) => {
+                let forward: Vec<Direction> = moves.iter().filter(|d| !d.is_opposite_to(last)).take(1).cloned().collect();
+                if forward.is_empty() { moves } else { forward }
+            },
+            _ => moves,
+        }
+    }
+
+    fn evaluate(&self, sim: &SimState, root_score: u32) -> f32 {
+        let me = sim.me();
+        let enemies: Vec<&Position> = sim.players
+            .iter()
+            .skip(1)
+            .filter(|p| p.alive)
+            .map(|p| &p.position)
+            .collect();
+
+        let threat_distance = sim.players
+            .iter()
+            .skip(1)
+            .filter(|p| p.alive && p.is_dangerous() && !me.is_dangerous())
+            .filter_map(|p| self.distances.distance(&p.position, &me.position))
+            .min()
+            .unwrap_or(SAFE_DISTANCE);
+
+        // Pellets we can get to before anyone else
+        let territory = sim.pellets()
+            .filter(|pellet| {
+                let mine = self.distances.distance(&me.position, pellet).unwrap_or(usize::MAX);
+                enemies.iter().all(|e| self.distances.distance(e, pellet).map(|d| d > mine).unwrap_or(true))
+            })
+            .count();
+
+        SCORE_WEIGHT * (me.score - root_score) as f32
+            + SAFETY_WEIGHT * threat_distance.min(SAFE_DISTANCE) as f32
+            + TERRITORY_WEIGHT * territory as f32
+    }
+}
+
+// Looks ahead over what we and the enemies around us can do, for the situations where the avoidance rules get it wrong
+pub struct Expectimax {
+    model: OpponentModel,
+    horizon: usize,
+    opponent_radius: usize,
+    max_opponents: usize,
+    max_nodes: usize,
+    budget: Duration,
+    scores: rules::Scores,
+}
+
+impl Expectimax {
+    pub fn check_params(params: &Params) -> Result<(), &'static str> {
+        match params.str_or("mode", "paranoid") {
+            "paranoid" | "expectimax" => Ok(()),
+            _ => Err("mode"),
+        }
+    }
+
+    pub fn from_params(params: &Params) -> Expectimax {
+        Expectimax {
+            model: match params.str_or("mode", "paranoid") {
+                "expectimax" => OpponentModel::Expectimax,
+                _ => OpponentModel::Paranoid,
+            },
+            horizon: params.usize_or("horizon", 10),
+            opponent_radius: params.usize_or("opponent_radius", 8),
+            max_opponents: params.usize_or("max_opponents", 2),
+            max_nodes: params.usize_or("max_nodes", 100_000),
+            budget: Duration::from_millis(params.usize_or("budget_ms", 20) as u64),
+            scores: params.scores(),
+        }
+    }
+
+    fn evaluate_moves(&self, bot: &Bot, state: &GameState) -> Vec<(Direction, f32)> {
+        let mut root = SimState::from_game_state(state, bot.remaining_ticks_dangerous).with_scores(self.scores);
+        if let Some(ref previous) = bot.previous_state {
+            root.infer_last_directions(previous);
+        }
+
+        // Only the closest enemies are worth searching over
+        let my_position = state.me.position();
+        let mut nearby: Vec<(usize, usize)> = state.enemies
+            .iter()
+            .enumerate()
+            .filter_map(|(i, e)| bot.distances.distance(&my_position, &e.position()).map(|d| (d, i + 1)))
+            .filter(|&(d, _)| d <= self.opponent_radius)
+            .collect();
+        nearby.sort();
+
+        if nearby.is_empty() {
+            return Vec::new();
+        }
+
+        // What we have learned about how the enemies move decides how likely their moves are
+        let opponents = nearby.into_iter().take(self.max_opponents).map(|(_, i)| i).collect();
+        Lookahead::new(&bot.map_information, &bot.distances, self.model, opponents, self.horizon, self.max_nodes)
+            .with_time_budget(self.budget)
+            .with_move_weights(|sim, player, choices| {
+                let enemy = &sim.players[player];
+                let context = Context {
+                    map: &state.map,
+                    distances: &bot.distances,
+                    pellet_distances: &bot.pellet_distances,
+                    me: sim.me().position.clone(),
+                };
+
+                let prediction = bot.opponents.predict(enemy.id, &context, &enemy.position, enemy.last_direction.as_ref());
+                choices
+                    .iter()
+                    .map(|c| prediction.iter().find(|&(d, _)| d == c).map(|&(_, p)| p).unwrap_or(0.0))
+                    .collect()
+            })
+            .evaluate_moves(&root)
+    }
+}
+
+impl Strategy for Expectimax {
+    fn description(&self) -> StrategyType {
+        StrategyType::Expectimax
+    }
+
+    fn action(&mut self, bot: &Bot, state: &GameState) -> Option<Direction> {
+        self.evaluate_moves(bot, state)
+            .into_iter()
+            .fold(None, |best: Option<(Direction, f32)>, (d, v)| match best {
+                Some((_, b)) if b >= v => best,
+                _ => Some((d, v)),
+            })
+            .map(|(d, _)| d)
+    }
+
+    fn candidates(&mut self, bot: &Bot, state: &GameState) -> Vec<Candidate> {
+        let values = self.evaluate_moves(bot, state);
+        let max = values.iter().map(|&(_, v)| v).fold(f32::MIN, f32::max);
+        let min = values.iter().map(|&(_, v)| v).fold(f32::MAX, f32::min);
+
+        values
+            .into_iter()
+            .map(|(direction, value)| {
+                let reason = format!("{:?} value of {:.1} over {} ticks", self.model, value, self.horizon);
+                Candidate::new(direction, if max > min { (value - min) / (max - min) } else { 1.0 }, reason)
+                    .with_score("value", value)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use protocol::Message;
+    use std::str::FromStr;
+
+    // A ring with a dangerous enemy three steps to the right of us
+    const STATE: &str = r#"{"gamestate":{"map":{"content":["|||||||||","|_______|","|_|||||_|","|_______|","|||||||||"],"height":5,"pelletsleft":0,"width":9},"others":[{"id":1,"isdangerous":true,"score":0,"x":6,"y":1}],"you":{"id":0,"isdangerous":false,"score":0,"x":3,"y":1}},"messagetype":"stateupdate"}"#;
+
+    fn state() -> GameState {
+        match Message::from_str(STATE).unwrap() {
+            Message::Update { state } => state,
+            _ => panic!(),
+        }
+    }
+
+    fn value_of(values: &[(Direction, f32)], direction: Direction) -> f32 {
+        values.iter().find(|(d, _)| *d == direction).unwrap().1
+    }
+
+    #[test]
+    fn should_run_away_from_dangerous_enemy() {
+        let state = state();
+        let map_information = MapInformation::from_map(&state.map);
+        let distances = DistanceTable::from_map(&state.map);
+        let root = SimState::from_game_state(&state, 0);
+
+        for model in &[OpponentModel::Paranoid, OpponentModel::Expectimax] {
+            let values = Lookahead::new(&map_information, &distances, *model, vec![1], 8, 100_000).evaluate_moves(&root);
+            assert!(value_of(&values, Direction::Left) > value_of(&values, Direction::Right), "{:?}", model);
+        }
+    }
+
+    #[test]
+    fn paranoid_should_never_be_more_optimistic() {
+        let state = state();
+        let map_information = MapInformation::from_map(&state.map);
+        let distances = DistanceTable::from_map(&state.map);
+        let root = SimState::from_game_state(&state, 0);
+
+        let paranoid = Lookahead::new(&map_information, &distances, OpponentModel::Paranoid, vec![1], 6, 100_000).evaluate_moves(&root);
+        let expectimax = Lookahead::new(&map_information, &distances, OpponentModel::Expectimax, vec![1], 6, 100_000).evaluate_moves(&root);
+        for &(ref d, v) in &paranoid {
+            assert!(v <= value_of(&expectimax, d.clone()));
+        }
+    }
+
+    #[test]
+    fn should_stop_when_out_of_time() {
+        let state = state();
+        let map_information = MapInformation::from_map(&state.map);
+        let distances = DistanceTable::from_map(&state.map);
+        let root = SimState::from_game_state(&state, 0);
+
+        // Far too deep to finish, but every move still gets its share of the time
+        let start = Instant::now();
+        let values = Lookahead::new(&map_information, &distances, OpponentModel::Expectimax, vec![1], 60, usize::MAX)
+            .with_time_budget(Duration::from_millis(20))
+            .evaluate_moves(&root);
+        assert!(start.elapsed() < Duration::from_secs(1));
+        assert_eq!(root.legal_moves(0).len(), values.len());
+    }
+}
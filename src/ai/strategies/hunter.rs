@@ -1,7 +1,7 @@
-use ai::{Bot, Strategy};
-use ai::strategies::StrategyType;
-use common::{Direction, Position};
-use protocol::GameState;
+use ai::{Bot, interception, Strategy};
+use ai::strategies::{Candidate, StrategyType};
+use common::Direction;
+use protocol::{GameState, Player};
 use traits::HasPosition;
 
 pub struct Hunter;
@@ -10,32 +10,64 @@ impl Hunter {
     pub fn new() -> Hunter {
         Hunter { }
     }
-}
 
-impl Strategy for Hunter {
-    fn description(&self) -> StrategyType {
-        StrategyType::Hunter
+    // We have to still be dangerous when we get there, and the enemy must not be anymore
+    fn is_edible(bot: &Bot, enemy: &Player, ticks: usize) -> bool {
+        ticks < bot.remaining_ticks_dangerous as usize && (bot.opponents.remaining_dangerous_ticks(enemy.id) as usize) < ticks
     }
 
-    fn action(&mut self, bot: &Bot, state: &GameState) -> Option<Direction> {
+    fn hunt(&self, bot: &Bot, state: &GameState) -> Option<Candidate> {
         if !bot.can_eat_others() {
             return None;
         }
 
-        let path: Option<Vec<Position>> = state.enemies
+        // Enemies in dead ends have nowhere to run
+        let cornered = state.enemies
             .iter()
-            .filter(|x| !x.is_dangerous) // TODO: Also hunt people that will not be dangerous for as long as me
             .filter(|x| bot.map_information.is_dead_end(&x.position()))
-            .map(|x| bot.path_graph.path_to(&x.position()))
-            .filter(|path| path.is_some())
-            .map(|path| path.unwrap())
-            .min_by(|p1, p2| {
-                p1.len().cmp(&p2.len())
-            });
-
-        match path {
-            Some(p) => state.me.position().direction_to(&state.map, &p.last().unwrap()),
-            None => None,
+            .filter_map(|x| bot.path_graph.path_to(&x.position()).map(|path| (x, path)))
+            .filter(|&(x, ref path)| Hunter::is_edible(bot, x, path.len()))
+            .min_by_key(|(_, path)| path.len());
+
+        if let Some((enemy, path)) = cornered {
+            return state.me.position()
+                .direction_to(&state.map, path.last().unwrap())
+                .map(|d| {
+                    Candidate::new(d, 1.0, format!("Enemy {} is stuck in a dead end {} steps away", enemy.id, path.len()))
+                        .with_target(enemy.position())
+                        .with_score("steps", path.len() as f32)
+                        .with_path(path)
+                });
         }
+
+        // Out in the open we cut off their escape routes instead of running after them
+        state.enemies
+            .iter()
+            .filter_map(|x| {
+                interception::intercept(&state.map, bot.map_information.walkable_positions(), &bot.distances, &state.me.position(), &x.position())
+                    .map(|i| (x, i))
+            })
+            .filter(|&(x, ref i)| Hunter::is_edible(bot, x, i.ticks_to_catch))
+            .min_by_key(|(_, i)| i.ticks_to_catch)
+            .map(|(x, i)| {
+                Candidate::new(i.direction, 1.0, format!("Intercepting enemy {}, caught within {} ticks", x.id, i.ticks_to_catch))
+                    .with_target(x.position())
+                    .with_score("ticks_to_catch", i.ticks_to_catch as f32)
+                    .with_score("escape_tiles", i.escape_tiles as f32)
+            })
+    }
+}
+
+impl Strategy for Hunter {
+    fn description(&self) -> StrategyType {
+        StrategyType::Hunter
+    }
+
+    fn action(&mut self, bot: &Bot, state: &GameState) -> Option<Direction> {
+        self.hunt(bot, state).map(|c| c.direction)
+    }
+
+    fn candidates(&mut self, bot: &Bot, state: &GameState) -> Vec<Candidate> {
+        self.hunt(bot, state).into_iter().collect()
     }
 }
@@ -1,10 +1,10 @@
 use itertools::Itertools;
+use std::collections::HashSet;
 
-use ai::strategies::StrategyType;
-use ai::{Bot, Strategy, pathfinder};
+use ai::strategies::{Candidate, StrategyType};
+use ai::{Bot, pathfinder, Strategy};
 use common::{Direction, Position};
-use game::Map;
-use protocol::{GameState, Player};
+use protocol::GameState;
 use traits::HasPosition;
 
 pub struct Killer;
@@ -14,42 +14,30 @@ impl Killer {
         Killer { }
     }
 
-    fn shortest_enemy_path_cost(map: &Map, enemies: &[Player], to: &Position) -> usize {
-        enemies
-            .iter()
-            .map(|e| pathfinder::get_shortest(map, &e.position(), to))
-            .filter(|path| path.is_some())
-            .map(|path| path.unwrap().len())
-            .min()
-            .and_then(|d| {
-                println!("Closest enemy distance to {} is {}", to, d);
-                Some(d)
-            })
-            .unwrap_or(usize::max_value())
-    }
-}
-
-impl Strategy for Killer {
-    fn description(&self) -> StrategyType {
-        StrategyType::Killer
-    }
-
-    fn action(&mut self, bot: &Bot, state: &GameState) -> Option<Direction> {
+    fn kill(&self, bot: &Bot, state: &GameState) -> Option<Candidate> {
         let remaining_super_pellets = state.map.super_pellets();
         if !bot.can_eat_others() && remaining_super_pellets.len() == 0 {
             return None;
         }
 
-        let path: Option<Vec<Position>> = state.enemies
+        let me = state.me.position();
+        let edible: HashSet<Position> = state.enemies
             .iter()
             .filter(|x| !x.is_dangerous) // TODO: Also hunt people that will not be dangerous for as long as me
-            .map(|x| bot.path_graph.path_to(&x.position()))
-            .filter(|path| path.is_some())
-            .map(|path| path.unwrap())
-            .filter(|path| path.len() < bot.remaining_ticks_dangerous as usize)
-            .min_by(|p1, p2| {
-                p1.len().cmp(&p2.len())
-            });
+            .map(|x| x.position())
+            .filter(|p| *p != me)
+            .collect();
+
+        // Only the ones we get to while we can still eat them
+        let path: Option<Vec<Position>> = if edible.is_empty() || !bot.can_eat_others() {
+            None
+        }
+        else {
+            pathfinder::PathQuery::new(&state.map, &me)
+                .any_of(edible)
+                .max_depth(bot.remaining_ticks_dangerous as usize - 1)
+                .find()
+        };
 
         let distance_to_closest_eatable_player = path
             .as_ref()
@@ -66,21 +54,41 @@ impl Strategy for Killer {
                 .map(|(cost, pos)| (cost.unwrap(), pos))
                 .sorted_by(|&(c1, _), &(c2, _)| c1.cmp(&c2))
                 .into_iter()
-                .find(|&(cost, pos)| cost < Killer::shortest_enemy_path_cost(&state.map, &state.enemies, pos))
+                .find(|&(_, pos)| bot.territory.is_ours(pos))
                 .and_then(|(_, pos)| bot.path_graph.path_to(&pos));
 
             return match path_to_super_pellet {
                 None => None,
-                Some(path) => {
-                    println!("I found a super pellet at {} that I can get to in {} ticks", path[0], path.len());
-                    state.me.position().direction_to(&state.map, &path.last().unwrap())
-                },
+                Some(path) => state.me.position().direction_to(&state.map, path.last().unwrap()).map(|d| {
+                    Candidate::new(d, 1.0, format!("Going for the super pellet at {}, {} ticks away", path[0], path.len()))
+                        .with_target(path[0].clone())
+                        .with_path(path)
+                }),
             }
         }
 
         match path {
-            Some(p) => state.me.position().direction_to(&state.map, &p.last().unwrap()),
+            Some(p) => state.me.position().direction_to(&state.map, p.last().unwrap()).map(|d| {
+                Candidate::new(d, 1.0, format!("Chasing the enemy at {}, {} ticks away", p[0], p.len()))
+                    .with_target(p[0].clone())
+                    .with_score("remaining_ticks_dangerous", bot.remaining_ticks_dangerous as f32)
+                    .with_path(p)
+            }),
             None => None,
         }
     }
 }
+
+impl Strategy for Killer {
+    fn description(&self) -> StrategyType {
+        StrategyType::Killer
+    }
+
+    fn action(&mut self, bot: &Bot, state: &GameState) -> Option<Direction> {
+        self.kill(bot, state).map(|c| c.direction)
+    }
+
+    fn candidates(&mut self, bot: &Bot, state: &GameState) -> Vec<Candidate> {
+        self.kill(bot, state).into_iter().collect()
+    }
+}
@@ -0,0 +1,270 @@
+use rand::{Rng, SeedableRng, XorShiftRng};
+use std::time::{Duration, Instant};
+
+use ai::config::Params;
+use ai::rollout::{self, RolloutPolicy};
+use ai::strategies::{Candidate, StrategyType};
+use ai::{Bot, Strategy};
+use common::{Direction, rules};
+use game::{DistanceTable, SimState};
+use protocol::GameState;
+
+// Played moves and how well they went, for one of our moves from the current tick
+#[derive(Clone, Debug)]
+pub struct MoveStatistics {
+    pub direction: Direction,
+    pub visits: u32,
+    pub mean_reward: f32,
+}
+
+// Nodes only branch on our own moves, enemies are moved by the rollout policy every time a node is passed.
+// The statistics of a node are then an average over what the enemies might do.
+struct Node {
+    direction: Option<Direction>,
+    parent: Option<usize>,
+    children: Vec<usize>,
+    untried: Vec<Direction>,
+
+    visits: u32,
+    total_reward: f32,
+}
+
+impl Node {
+    fn new(direction: Option<Direction>, parent: Option<usize>, untried: Vec<Direction>) -> Node {
+        Node {
+            direction,
+            parent,
+            children: Vec::new(),
+            untried,
+            visits: 0,
+            total_reward: 0.0,
+        }
+    }
+
+    fn mean_reward(&self) -> f32 {
+        if self.visits == 0 { 0.0 } else { self.total_reward / self.visits as f32 }
+    }
+}
+
+pub struct MctsPlanner {
+    rollout_depth: usize,
+    exploration: f32,
+    own_policy: Box<dyn RolloutPolicy>,
+    opponent_policy: Box<dyn RolloutPolicy>,
+}
+
+impl MctsPlanner {
+    pub fn new(rollout_depth: usize, exploration: f32, own_policy: Box<dyn RolloutPolicy>, opponent_policy: Box<dyn RolloutPolicy>) -> MctsPlanner {
+        MctsPlanner {
+            // Nothing to evaluate without looking at least a tick ahead
+            rollout_depth: rollout_depth.max(1),
+            exploration,
+            own_policy,
+            opponent_policy,
+        }
+    }
+
+    // Searches until the deadline, or until the maximum number of iterations if there is one
+    pub fn search(&self, root: &SimState, distances: &DistanceTable, deadline: Instant, max_iterations: Option<usize>, rng: &mut XorShiftRng) -> Vec<MoveStatistics> {
+        let mut nodes = vec![Node::new(None, None, root.legal_moves(0))];
+
+        let mut iterations = 0;
+        while max_iterations.map(|max| iterations < max).unwrap_or(true) && Instant::now() < deadline {
+            iterations += 1;
+
+            let mut sim = root.clone();
+            let mut current = 0;
+            let mut depth = 0;
+
+            // Selection and expansion
+            while sim.me().alive && depth < self.rollout_depth {
+                if !nodes[current].untried.is_empty() {
+                    let index = rng.gen_range(0, nodes[current].untried.len());
+                    let direction = nodes[current].untried.swap_remove(index);
+                    self.step(&mut sim, &direction, distances, rng);
+                    depth += 1;
+
+                    let child = Node::new(Some(direction), Some(current), sim.legal_moves(0));
+                    nodes.push(child);
+                    let child_index = nodes.len() - 1;
+                    nodes[current].children.push(child_index);
+                    current = child_index;
+                    break;
+                }
+
+                match self.select_child(&nodes, current) {
+                    Some(child) => {
+                        let direction = nodes[child].direction.clone().unwrap();
+                        self.step(&mut sim, &direction, distances, rng);
+                        depth += 1;
+                        current = child;
+                    },
+                    None => break,
+                }
+            }
+
+            // Simulation
+            while sim.me().alive && depth < self.rollout_depth {
+                let direction = self.own_policy.choose(&sim, 0, distances, rng);
+                self.step(&mut sim, &direction, distances, rng);
+                depth += 1;
+            }
+
+            // Backpropagation
+            let reward = self.evaluate(root, &sim);
+            let mut node = Some(current);
+            while let Some(index) = node {
+                nodes[index].visits += 1;
+                nodes[index].total_reward += reward;
+                node = nodes[index].parent;
+            }
+        }
+
+        nodes[0].children
+            .iter()
+            .map(|&i| MoveStatistics {
+                direction: nodes[i].direction.clone().unwrap(),
+                visits: nodes[i].visits,
+                mean_reward: nodes[i].mean_reward(),
+            })
+            .collect()
+    }
+
+    // UCB1
+    fn select_child(&self, nodes: &[Node], parent: usize) -> Option<usize> {
+        let log_visits = (nodes[parent].visits.max(1) as f32).ln();
+        nodes[parent].children
+            .iter()
+            .map(|&i| (i, nodes[i].mean_reward() + self.exploration * (log_visits / nodes[i].visits.max(1) as f32).sqrt()))
+            .fold(None, |best: Option<(usize, f32)>, (i, value)| match best {
+                Some((_, v)) if v >= value => best,
+                _ => Some((i, value)),
+            })
+            .map(|(i, _)| i)
+    }
+
+    fn step(&self, sim: &mut SimState, direction: &Direction, distances: &DistanceTable, rng: &mut XorShiftRng) {
+        let mut moves = Vec::with_capacity(sim.players.len());
+        moves.push(direction.clone());
+        for player in 1..sim.players.len() {
+            moves.push(self.opponent_policy.choose(sim, player, distances, rng));
+        }
+
+        sim.apply(&moves);
+    }
+
+    // Dying is the worst that can happen, otherwise the more points the better
+    fn evaluate(&self, root: &SimState, sim: &SimState) -> f32 {
+        if !sim.me().alive {
+            return 0.0;
+        }
+
+        let gained = (sim.me().score - root.me().score) as f32;
+        let possible = (self.rollout_depth as u32 * root.scores().pellet) as f32;
+        0.5 + 0.5 * (gained / possible).min(1.0)
+    }
+}
+
+// Looks a number of ticks ahead by playing out random games, to see the traps the one-step strategies walk in to
+pub struct Mcts {
+    planner: MctsPlanner,
+    budget: Duration,
+    max_iterations: Option<usize>,
+    scores: rules::Scores,
+    rng: XorShiftRng,
+}
+
+impl Mcts {
+    // Params that can't be used, from_params falls back to the defaults for them
+    pub fn check_params(params: &Params) -> Result<(), &'static str> {
+        if params.usize_or("rollout_depth", 20) == 0 {
+            return Err("rollout_depth");
+        }
+
+        match ["own_policy", "opponent_policy"].iter().find(|&&name| rollout::from_name(params.str_or(name, "greedy")).is_none()) {
+            Some(name) => Err(name),
+            None => Ok(()),
+        }
+    }
+
+    pub fn from_params(params: &Params) -> Mcts {
+        let policy = |name: &str, default: &str| rollout::from_name(params.str_or(name, default))
+            .unwrap_or_else(|| rollout::from_name(default).unwrap());
+
+        Mcts {
+            planner: MctsPlanner::new(
+                params.usize_or("rollout_depth", 20),
+                params.f32_or("exploration", 1.4),
+                policy("own_policy", "greedy"),
+                policy("opponent_policy", "chaser")),
+            budget: Duration::from_millis(params.usize_or("budget_ms", 20) as u64),
+            max_iterations: match params.usize_or("max_iterations", 0) {
+                0 => None,
+                n => Some(n),
+            },
+            scores: params.scores(),
+            rng: XorShiftRng::from_seed([0x1234_5678, 0x9abc_def0, 0x0fed_cba9, 0x8765_4321]),
+        }
+    }
+
+    fn statistics(&mut self, bot: &Bot, state: &GameState) -> Vec<MoveStatistics> {
+        let root = SimState::from_game_state(state, bot.remaining_ticks_dangerous).with_scores(self.scores);
+        let deadline = Instant::now() + self.budget;
+        self.planner.search(&root, &bot.distances, deadline, self.max_iterations, &mut self.rng)
+    }
+}
+
+impl Strategy for Mcts {
+    fn description(&self) -> StrategyType {
+        StrategyType::Mcts
+    }
+
+    fn action(&mut self, bot: &Bot, state: &GameState) -> Option<Direction> {
+        self.statistics(bot, state)
+            .into_iter()
+            .max_by_key(|s| s.visits)
+            .map(|s| s.direction)
+    }
+
+    fn candidates(&mut self, bot: &Bot, state: &GameState) -> Vec<Candidate> {
+        self.statistics(bot, state)
+            .into_iter()
+            .map(|s| {
+                Candidate::new(s.direction, s.mean_reward, format!("{} rollouts with a mean reward of {:.2}", s.visits, s.mean_reward))
+                    .with_score("visits", s.visits as f32)
+                    .with_score("mean_reward", s.mean_reward)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use protocol::Message;
+    use std::str::FromStr;
+
+    #[test]
+    fn should_not_walk_in_to_dangerous_enemy() {
+        // A ring with a dangerous enemy coming from the right, pellets in both directions
+        const STATE: &str = r#"{"gamestate":{"map":{"content":["|||||||||","|.......|","|.|||||.|","|.......|","|||||||||"],"height":5,"pelletsleft":18,"width":9},"others":[{"id":1,"isdangerous":true,"score":0,"x":6,"y":1}],"you":{"id":0,"isdangerous":false,"score":0,"x":3,"y":1}},"messagetype":"stateupdate"}"#;
+        let state = match Message::from_str(STATE).unwrap() {
+            Message::Update { state } => state,
+            _ => panic!(),
+        };
+
+        let distances = DistanceTable::from_map(&state.map);
+        let planner = MctsPlanner::new(12, 1.4, Box::new(rollout::GreedyPellets), Box::new(rollout::Chaser));
+        let mut rng = XorShiftRng::from_seed([1, 2, 3, 4]);
+
+        let root = SimState::from_game_state(&state, 0);
+        let deadline = Instant::now() + Duration::from_secs(60);
+        let statistics = planner.search(&root, &distances, deadline, Some(2000), &mut rng);
+
+        let best = statistics.iter().max_by_key(|s| s.visits).unwrap();
+        assert_eq!(Direction::Left, best.direction);
+
+        let right = statistics.iter().find(|s| s.direction == Direction::Right).unwrap();
+        assert!(right.mean_reward < best.mean_reward);
+    }
+}
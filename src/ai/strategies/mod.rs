@@ -1,26 +1,200 @@
 use ai;
+use ai::config::Params;
 use common;
 use protocol;
 
 pub mod avoidance;
+pub mod cornering;
+pub mod endgame;
+pub mod expectimax;
 pub mod hunter;
 pub mod killer;
+pub mod mcts;
 pub mod pick_pellets;
+pub mod spawn_escape;
 
 pub use self::avoidance::Avoidance;
+pub use self::cornering::Cornering;
+pub use self::endgame::Endgame;
+pub use self::expectimax::Expectimax;
 pub use self::hunter::Hunter;
 pub use self::killer::Killer;
+pub use self::mcts::Mcts;
 pub use self::pick_pellets::PickPellets;
+pub use self::spawn_escape::SpawnEscape;
 
-#[derive(Debug, PartialEq)]
+// Every strategy that can be enabled from the configuration
+pub const NAMES: [&str; 9] = ["avoidance", "cornering", "endgame", "expectimax", "hunter", "killer", "mcts", "pick_pellets", "spawn_escape"];
+
+pub fn is_registered(name: &str) -> bool {
+    NAMES.contains(&name)
+}
+
+// Params that every strategy depending on the points awarded by the server reads, see `Params::scores`
+const SCORE_PARAMS: [&str; 3] = ["pellet_score", "super_pellet_score", "eat_player_score"];
+
+// Params each strategy reads, anything else given to it is a mistake
+pub fn param_names(name: &str) -> Vec<&'static str> {
+    let (own, scored): (&[&'static str], bool) = match name {
+        "avoidance" => (&["radius", "dead_end_margin", "warmup_ticks"], false),
+        "cornering" => (&["margin"], false),
+        "endgame" => (&["pellets_left"], true),
+        "expectimax" => (&["mode", "horizon", "opponent_radius", "max_opponents", "max_nodes", "budget_ms"], true),
+        "mcts" => (&["budget_ms", "rollout_depth", "exploration", "own_policy", "opponent_policy", "max_iterations"], true),
+        "pick_pellets" => (&["route_horizon"], false),
+        _ => (&[], false),
+    };
+
+    let mut names = own.to_vec();
+    if scored {
+        names.extend_from_slice(&SCORE_PARAMS);
+    }
+    names
+}
+
+// Params that hold a name or a fraction, all the others are counts of ticks, tiles or points
+const TEXT_PARAMS: [&str; 3] = ["mode", "own_policy", "opponent_policy"];
+const NUMBER_PARAMS: [&str; 1] = ["exploration"];
+
+// Checks the params that a strategy can't make sense of, returning the name of the first bad one
+pub fn check_params(name: &str, params: &Params) -> Result<(), &'static str> {
+    let mistyped = param_names(name).into_iter().find(|p| {
+        if TEXT_PARAMS.contains(p) {
+            !params.is_text(p)
+        }
+        else if NUMBER_PARAMS.contains(p) {
+            !params.is_number(p)
+        }
+        else {
+            !params.is_count(p)
+        }
+    });
+    if let Some(param) = mistyped {
+        return Err(param);
+    }
+
+    match name {
+        "expectimax" => Expectimax::check_params(params),
+        "mcts" => Mcts::check_params(params),
+        _ => Ok(()),
+    }
+}
+
+pub fn create(name: &str, params: &Params) -> Option<Box<dyn Strategy>> {
+    match name {
+        "avoidance" => Some(Box::new(Avoidance::from_params(params))),
+        "cornering" => Some(Box::new(Cornering::from_params(params))),
+        "endgame" => Some(Box::new(Endgame::from_params(params))),
+        "expectimax" => Some(Box::new(Expectimax::from_params(params))),
+        "hunter" => Some(Box::new(Hunter::new())),
+        "killer" => Some(Box::new(Killer::new())),
+        "mcts" => Some(Box::new(Mcts::from_params(params))),
+        "pick_pellets" => Some(Box::new(PickPellets::from_params(params))),
+        "spawn_escape" => Some(Box::new(SpawnEscape::new())),
+        _ => None,
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Serialize)]
 pub enum StrategyType {
     Avoidance,
+    Cornering,
+    Endgame,
+    Expectimax,
     Hunter,
     Killer,
+    Mcts,
     PickPellets,
+    SpawnEscape,
+}
+
+// What a strategy based a candidate on, for working out afterwards why it wanted to go somewhere
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct Rationale {
+    pub target: Option<common::Position>,
+    // Destination first, like the paths from the pathfinder
+    pub path: Vec<common::Position>,
+    // Enemies that were taken in to account
+    pub threats: Vec<u32>,
+    // The numbers that went in to the score
+    pub scores: Vec<(String, f32)>,
+    // Walks in to a wall on purpose, to stand still
+    pub waits: bool,
+}
+
+// A direction a strategy would like to go in, how much it wants to go there and why
+#[derive(Clone, Debug, Serialize)]
+pub struct Candidate {
+    pub direction: common::Direction,
+    // Between 0 and 1
+    pub score: f32,
+    pub reason: String,
+    pub rationale: Rationale,
+}
+
+impl Candidate {
+    pub fn new(direction: common::Direction, score: f32, reason: String) -> Candidate {
+        Candidate {
+            direction,
+            score,
+            reason,
+            rationale: Rationale::default(),
+        }
+    }
+
+    pub fn with_target(mut self, target: common::Position) -> Candidate {
+        self.rationale.target = Some(target);
+        self
+    }
+
+    pub fn with_path(mut self, path: Vec<common::Position>) -> Candidate {
+        self.rationale.path = path;
+        self
+    }
+
+    pub fn with_threats(mut self, threats: Vec<u32>) -> Candidate {
+        self.rationale.threats = threats;
+        self
+    }
+
+    pub fn with_score(mut self, name: &str, value: f32) -> Candidate {
+        self.rationale.scores.push((name.to_string(), value));
+        self
+    }
+
+    pub fn waiting(mut self) -> Candidate {
+        self.rationale.waits = true;
+        self
+    }
 }
 
 pub trait Strategy {
     fn description(&self) -> StrategyType;
     fn action(&mut self, bot: &ai::Bot, current_state: &protocol::GameState) -> Option<common::Direction>;
+
+    // Called when we die or a new round starts, anything a strategy remembers about the current life goes here
+    fn reset(&mut self) {}
+
+    // Strategies that can tell directions apart, or explain themselves, should override this
+    fn candidates(&mut self, bot: &ai::Bot, current_state: &protocol::GameState) -> Vec<Candidate> {
+        let description = self.description();
+        self.action(bot, current_state)
+            .map(|direction| Candidate::new(direction.clone(), 1.0, format!("{:?} wants to go {}", description, direction)))
+            .into_iter()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_create_every_registered_strategy() {
+        for name in &NAMES {
+            assert!(create(name, &Params::default()).is_some(), "{} is not created", name);
+        }
+
+        assert!(create("camper", &Params::default()).is_none());
+    }
 }
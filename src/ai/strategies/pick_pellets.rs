@@ -1,29 +1,63 @@
-use std::cmp;
 use std::collections::HashSet;
 
-use ai::strategies::StrategyType;
-use ai::{Bot, pathfinder, Strategy};
+use ai::config::Params;
+use ai::strategies::{Candidate, StrategyType};
+use ai::territory::Owner;
+use ai::{Bot, is_threat, pathfinder, Strategy};
 use common::{Direction, Position};
 use protocol::GameState;
 use traits::HasPosition;
 
+// An enemy that is less likely than this to be going for a pellet is considered to be heading elsewhere
+const UNCLAIMED: f32 = 0.2;
+
 pub struct PickPellets {
     target_pellet: Option<Position>,
+    // How many ticks ahead to plan routes
+    route_horizon: usize,
 }
 
 impl PickPellets {
-    pub fn new() -> PickPellets {
+    pub fn from_params(params: &Params) -> PickPellets {
         PickPellets {
             target_pellet: None,
+            route_horizon: params.usize_or("route_horizon", 30),
         }
     }
 
-    fn is_enemy_nearby(&self, bot: &Bot, state: &GameState) -> bool {
+    // Routes are not planned through enemies, what they'll get to first, or dead ends they can close off before
+    // we're back out
+    fn is_blocked(bot: &Bot, state: &GameState, position: &Position) -> bool {
+        if let Some(Owner::Enemy(_)) = bot.territory.owner_of(position) {
+            return true;
+        }
+
+        let dead_end = match bot.map_information.dead_end(position) {
+            Some(dead_end) => dead_end,
+            None => return false,
+        };
+        let back_out = bot.distances.distance(&state.me.position(), position).map(|d| d + dead_end.depth).unwrap_or(usize::MAX);
         state.enemies
             .iter()
-            .filter(|e| !bot.map_information.is_dead_end(&e.position()))
-            .find(|e| bot.path_graph.cost_to(&e.position()).unwrap_or(usize::max_value()) <= 3)
-            .is_some()
+            .filter(|e| is_threat(e, bot.can_eat_others()))
+            .any(|e| bot.distances.distance(&e.position(), &dead_end.exit).map(|d| d <= back_out).unwrap_or(false))
+    }
+
+    // Pellets that someone else will get to first are not worth going for, unless they are going somewhere else
+    fn pellet_value(bot: &Bot, state: &GameState, position: &Position) -> f32 {
+        if !state.map.tile_at(position).is_pellet() {
+            return 0.0;
+        }
+
+        match bot.territory.owner_of(position) {
+            Some(Owner::Me) => 1.0,
+            Some(Owner::Tie) => 0.5 * (1.0 - bot.intents.strongest_claim(&bot.distances, position)),
+            Some(Owner::Enemy(id)) => {
+                let claim = bot.intents.claim(id, &bot.distances, position);
+                if claim < UNCLAIMED { 0.5 * (1.0 - claim) } else { 0.0 }
+            },
+            None => 0.0,
+        }
     }
 }
 
@@ -43,43 +77,84 @@ impl Strategy for PickPellets {
             self.target_pellet = None;
         }
 
-        let enemy_positions: HashSet<Position> = state.enemies
-            .iter()
-            .map(|x| x.position())
-            .collect();
-
-        // Try to find the optimal path not containing any enemies
-        if let Some(path) = bot.map_information
-            .intersections()
-            .into_iter()
-            .map(|p| bot.path_graph.path_to(p))
-            .filter(|path| path.is_some())
-            .map(|path| path.unwrap())
-            .filter(|path| path.len() > 0)
-            .filter(|path| !path.iter().any(|pos| enemy_positions.contains(pos) || bot.map_information.is_dead_end(&pos)))
-            .map(|path| (state.map.points_in_path(&path) as f32 / path.len() as f32, path))
-            .max_by(|&(pp1, _), &(pp2, _)| {
-                pp1.partial_cmp(&pp2).unwrap_or(cmp::Ordering::Equal)
-            })
-            .map(|(_, path)| path) {
-            self.target_pellet = Some(path[0].clone())
+        // Plan a route through the corridors with the most pellets that we can get to before anyone else
+        if self.target_pellet.is_none() || !state.map.tile_at(&self.target_pellet.clone().unwrap()).is_pellet() {
+            let enemy_positions: HashSet<Position> = state.enemies
+                .iter()
+                .map(|x| x.position())
+                .collect();
+
+            let pellet_value = |p: &Position| PickPellets::pellet_value(bot, state, p);
+            let is_blocked = |p: &Position| enemy_positions.contains(p) || PickPellets::is_blocked(bot, state, p);
+            self.target_pellet = bot.route_planner
+                .plan(&state.me.position(), self.route_horizon, &pellet_value, is_blocked)
+                .and_then(|path| path.into_iter().rev().find(|p| pellet_value(p) > 0.0));
         }
 
         if self.target_pellet.is_none() || !state.map.tile_at(&self.target_pellet.clone().unwrap()).is_pellet() {
-            let path: Option<Vec<Position>> = pathfinder::find_closest_pellet(&state.map, &state.me.position(), &state.enemies, |_| true);
-            if let Some(p) = path {
-                self.target_pellet = Some(p[0].clone());
-            } else {
-                self.target_pellet = None;
-            }
+            // Prefer pellets that we can reach before anyone else
+            self.target_pellet = pathfinder::find_closest_pellet(&state.map, &state.me.position(), &state.enemies, |p| bot.territory.is_ours(p))
+                .map(|path| path[0].clone())
+                .or_else(|| bot.pellet_distances.closest_source_to(&state.me.position()).cloned());
         }
 
         if let &Some(ref pos) = &self.target_pellet {
-            return pathfinder::get_shortest_no_enemies(&state.map, &state.me.position(), &pos, &state.enemies)
-                .and_then(|path| Some(path.last().unwrap().clone()))
-                .and_then(|pos| state.me.position().direction_to(&state.map, &pos));
+            return bot.predicted_safe_path_to(state, &pos)
+                .or_else(|| bot.safest_path_to(state, &pos))
+                .or_else(|| pathfinder::PathQuery::new(&state.map, &state.me.position()).to(&pos).avoiding(&state.enemies).find())
+                .and_then(|path| pathfinder::first_direction(&state.map, &state.me.position(), &path));
         }
 
         None
     }
+
+    fn reset(&mut self) {
+        self.target_pellet = None;
+    }
+
+    fn candidates(&mut self, bot: &Bot, state: &GameState) -> Vec<Candidate> {
+        let direction = self.action(bot, state);
+        match (direction, &self.target_pellet) {
+            (Some(direction), &Some(ref target)) => {
+                // The paths we follow only walk in to walls when they wait for an enemy to pass
+                let waits = !state.map.tile_at(&state.me.position().adjacent(&state.map, &direction)).is_walkable();
+                let candidate = Candidate::new(direction, 1.0, format!("Heading for the pellet at {}", target))
+                    .with_target(target.clone())
+                    .with_path(bot.distances.path(&state.me.position(), target).unwrap_or_default())
+                    .with_score("value", PickPellets::pellet_value(bot, state, target));
+                vec![if waits { candidate.waiting() } else { candidate }]
+            },
+            _ => Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use protocol::Message;
+    use std::str::FromStr;
+
+    // A loop with a dead end hanging off the bottom, the enemy is a step closer to its exit than we are
+    fn parse() -> GameState {
+        let message = r#"{"messagetype":"stateupdate","gamestate":{"map":{"content":["|||||||||","|_______|","|_|||||_|","|_______|","||||_||||","||||.||||","|||||||||"],"height":7,"width":9,"pelletsleft":1},"you":{"id":0,"x":1,"y":3},"others":[{"id":1,"x":7,"y":2}]}}"#;
+        match Message::from_str(message).unwrap() {
+            Message::Update { state } => state,
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn should_not_plan_through_enemy_territory_or_dead_ends() {
+        let mut bot = Bot::from_game_state(&parse());
+        bot.determine_action(parse());
+        let state = parse();
+
+        assert!(!PickPellets::is_blocked(&bot, &state, &Position::new(2, 3)));
+        assert!(PickPellets::is_blocked(&bot, &state, &Position::new(6, 3)));
+
+        // We get to the pellet first, but not back out before the enemy gets to the exit
+        assert_eq!(Some(Owner::Me), bot.territory.owner_of(&Position::new(4, 5)));
+        assert!(PickPellets::is_blocked(&bot, &state, &Position::new(4, 5)));
+    }
 }
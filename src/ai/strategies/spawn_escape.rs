@@ -0,0 +1,114 @@
+use ai::strategies::{Candidate, StrategyType};
+use ai::{Bot, is_threat, Strategy};
+use common::{Direction, Position};
+use game::{SpawnExit, TileType};
+use protocol::{GameState, Player};
+use traits::HasPosition;
+
+// Gets us out of the spawn area. Everyone starts out harmless, so there is nothing to avoid in there, what matters
+// is leaving through the door the others are furthest away from instead of following them out.
+pub struct SpawnEscape {
+    // The tile outside the door we're leaving through
+    exit: Option<Position>,
+}
+
+impl SpawnEscape {
+    pub fn new() -> SpawnEscape {
+        SpawnEscape {
+            exit: None,
+        }
+    }
+
+    fn choose_exit(bot: &Bot, state: &GameState) -> Option<Position> {
+        let me = state.me.position();
+        bot.map_information
+            .spawn_exits()
+            .iter()
+            .filter_map(|exit| {
+                let mine = bot.distances.distance(&me, &exit.outside)?;
+                let distance_to = |e: &Player| bot.distances.distance(&e.position(), &exit.outside);
+
+                // Don't walk out right in front of someone who can eat us or block the way
+                let threatened = state.enemies
+                    .iter()
+                    .filter(|e| is_threat(e, bot.can_eat_others()))
+                    .any(|e| distance_to(e).map(|d| d <= mine + 1).unwrap_or(false));
+                if threatened {
+                    return None;
+                }
+
+                let others = state.enemies.iter().filter_map(distance_to).min().unwrap_or(usize::MAX / 2);
+                Some((exit, others as i64 - mine as i64, mine))
+            })
+            .fold(None, |best: Option<(&SpawnExit, i64, usize)>, candidate| match best {
+                Some(ref b) if (b.1, -(b.2 as i64)) >= (candidate.1, -(candidate.2 as i64)) => best,
+                _ => Some(candidate),
+            })
+            .map(|(exit, _, _)| exit.outside.clone())
+    }
+
+    fn leave(&mut self, bot: &Bot, state: &GameState) -> Option<(Direction, Position)> {
+        let me = state.me.position();
+        if !bot.map_information.is_in_spawn(&me) && state.map.tile_at(&me) != TileType::Door {
+            self.exit = None;
+            return None;
+        }
+
+        if self.exit.is_none() {
+            self.exit = SpawnEscape::choose_exit(bot, state);
+        }
+
+        let exit = self.exit.clone()?;
+        bot.distances.next_hop(&me, &exit).map(|d| (d, exit))
+    }
+}
+
+impl Strategy for SpawnEscape {
+    fn description(&self) -> StrategyType {
+        StrategyType::SpawnEscape
+    }
+
+    fn action(&mut self, bot: &Bot, state: &GameState) -> Option<Direction> {
+        self.leave(bot, state).map(|(direction, _)| direction)
+    }
+
+    fn reset(&mut self) {
+        self.exit = None;
+    }
+
+    fn candidates(&mut self, bot: &Bot, state: &GameState) -> Vec<Candidate> {
+        self.leave(bot, state)
+            .map(|(direction, exit)| {
+                Candidate::new(direction, 1.0, format!("Leaving the spawn area towards {}", exit))
+                    .with_path(bot.distances.path(&state.me.position(), &exit).unwrap_or_default())
+                    .with_threats(state.enemies.iter().filter(|e| is_threat(e, bot.can_eat_others())).map(|e| e.id).collect())
+                    .with_target(exit)
+            })
+            .into_iter()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use protocol::Message;
+    use std::str::FromStr;
+
+    const MAP: &str = r#"{"content":["||||||||||||||||||||||||||||","|............||............|","|.||||.|||||.||.|||||.||||.|","|o||||.|||||.||.|||||.||||o|","|.||||.|||||.||.|||||.||||.|","|....|................|....|","|.||||.||.||||||||.||.||||.|","|.||||.||.||||||||.||.||||.|","|....|.||....||....||.|....|","||||||.|||||_||_|||||.||||||","_____|.|||||_||_|||||.|_____","_____|.||__________||.|_____","_____|.||_|||--|||_||.|_____","||||||.||_|______|_||.||||||","______.___|______|___.______","||||||.||_|______|_||.||||||","_____|.||_|||--|||_||.|_____","_____|.||__________||.|_____","_____|.||_||||||||_||.|_____","||||||.||_||||||||_||.||||||","|....|.......||.......|....|","|.||||.|||||.||.|||||.||||.|","|.||||.|||||.||.|||||.||||.|","|o..||.......__.......||..o|","|||.||.||.||||||||.||.||.|||","|||.||.||.||||||||.||.||.|||","|......||....||....||......|","|.||||||||||.||.||||||||||.|","|.||||||||||.||.||||||||||.|","|..........................|","||||||||||||||||||||||||||||"],"height":31,"pelletsleft":238,"width":28}"#;
+
+    #[test]
+    fn should_leave_through_the_door_away_from_others() {
+        // Someone already made it out of the top doors, so we take the bottom ones
+        let message = format!(r#"{{"messagetype":"stateupdate","gamestate":{{"map":{},"you":{{"id":0,"x":12,"y":14}},"others":[{{"id":1,"x":11,"y":11}}]}}}}"#, MAP);
+        let state = match Message::from_str(&message).unwrap() {
+            Message::Update { state } => state,
+            _ => panic!(),
+        };
+
+        let bot = Bot::from_game_state(&state);
+        let mut spawn_escape = SpawnEscape::new();
+        assert!(spawn_escape.action(&bot, &state).is_some());
+        assert_eq!(17, spawn_escape.exit.clone().unwrap().y);
+    }
+}
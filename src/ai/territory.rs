@@ -0,0 +1,97 @@
+use ai::distance_field::DistanceField;
+use common::Position;
+use game;
+use protocol::Player;
+use traits::HasPosition;
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Owner {
+    Me,
+    Enemy(u32),
+    Tie,
+}
+
+// Keeps track of who can reach each tile first, a voronoi partitioning of the map based on walking distance
+pub struct Territory {
+    field: DistanceField,
+    owners: Vec<Owner>,
+}
+
+impl Territory {
+    pub fn new(map: &game::Map) -> Territory {
+        Territory {
+            field: DistanceField::new(map),
+            owners: Vec::new(),
+        }
+    }
+
+    pub fn update_from_map(&mut self, map: &game::Map, my_position: &Position, enemies: &[Player]) {
+        // The sources of the distance field are in the same order as the owners
+        self.owners.clear();
+        self.owners.push(Owner::Me);
+        self.owners.extend(enemies.iter().map(|e| Owner::Enemy(e.id)));
+
+        let sources = Some(my_position.clone())
+            .into_iter()
+            .chain(enemies.iter().map(|e| e.position()));
+        self.field.update_from_map(map, sources);
+    }
+
+    pub fn owner_of(&self, position: &Position) -> Option<Owner> {
+        self.field
+            .closest_at(position)
+            .map(|closest| match self.field.runner_up_at(position) {
+                Some(ref runner_up) if runner_up.distance == closest.distance => Owner::Tie,
+                _ => self.owners[closest.source],
+            })
+    }
+
+    pub fn is_ours(&self, position: &Position) -> bool {
+        self.owner_of(position) == Some(Owner::Me)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json;
+    use game::Map;
+
+    const CORRIDOR: &'static str = r#"
+{
+    "content": [
+        "|||||||||",
+        "|_______|",
+        "||||_||||",
+        "||||_||||",
+        "|||||||||"
+    ],
+    "height": 5,
+    "pelletsleft": 0,
+    "width": 9
+}"#;
+
+    #[test]
+    fn can_split_territory_between_players() {
+        let map: Map = serde_json::from_str(CORRIDOR).unwrap();
+        let enemies: Vec<Player> = serde_json::from_str(r#"[{"id":3,"x":7,"y":1}]"#).unwrap();
+
+        let mut territory = Territory::new(&map);
+        territory.update_from_map(&map, &Position::new(1, 1), &enemies);
+
+        assert_eq!(Some(Owner::Me), territory.owner_of(&Position::new(3, 1)));
+        assert_eq!(Some(Owner::Tie), territory.owner_of(&Position::new(4, 1)));
+        assert_eq!(Some(Owner::Tie), territory.owner_of(&Position::new(4, 3)));
+        assert_eq!(Some(Owner::Enemy(3)), territory.owner_of(&Position::new(6, 1)));
+    }
+
+    #[test]
+    fn owns_everything_without_enemies() {
+        let map: Map = serde_json::from_str(CORRIDOR).unwrap();
+
+        let mut territory = Territory::new(&map);
+        territory.update_from_map(&map, &Position::new(1, 1), &[]);
+
+        assert!(territory.is_ours(&Position::new(4, 3)));
+    }
+}
@@ -0,0 +1,161 @@
+use serde_json;
+use std::fs::File;
+use std::io;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use ai::arbiter::{Breakdown, Proposal};
+use ai::opponents::{Classification, OpponentTracker};
+use common::{Direction, Position};
+use protocol::{GameState, Player};
+use traits::HasPosition;
+
+#[derive(Serialize)]
+struct PlayerSummary {
+    id: u32,
+    position: Position,
+    score: u32,
+    dangerous: bool,
+    // How the enemy seems to play, once it has moved
+    #[serde(skip_serializing_if = "Option::is_none")]
+    classification: Option<Classification>,
+}
+
+impl<'a> From<&'a Player> for PlayerSummary {
+    fn from(player: &'a Player) -> PlayerSummary {
+        PlayerSummary {
+            id: player.id,
+            position: player.position(),
+            score: player.score,
+            dangerous: player.is_dangerous,
+            classification: None,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Record<'a> {
+    tick: u32,
+    me: PlayerSummary,
+    enemies: Vec<PlayerSummary>,
+    pellets_left: usize,
+    remaining_ticks_dangerous: u32,
+    policy: &'static str,
+    proposals: &'a [Proposal],
+    winner: Option<usize>,
+    explanation: &'a str,
+    decision: &'a Direction,
+}
+
+// Writes a line of JSON for every tick, with what we saw, what every strategy wanted and what we ended up doing
+pub struct Tracer {
+    out: Box<dyn Write>,
+}
+
+impl Tracer {
+    pub fn new(out: Box<dyn Write>) -> Tracer {
+        Tracer {
+            out,
+        }
+    }
+
+    pub fn create<P: AsRef<Path>>(path: P) -> io::Result<Tracer> {
+        let file = File::create(path)?;
+        Ok(Tracer::new(Box::new(BufWriter::new(file))))
+    }
+
+    pub fn record(&mut self, state: &GameState, opponents: &OpponentTracker, remaining_ticks_dangerous: u32, breakdown: &Breakdown, decision: &Direction) -> io::Result<()> {
+        let enemies = state.enemies
+            .iter()
+            .map(|e| PlayerSummary { classification: opponents.classify(e.id), ..PlayerSummary::from(e) })
+            .collect();
+
+        let record = Record {
+            tick: breakdown.tick,
+            me: PlayerSummary::from(&state.me),
+            enemies,
+            pellets_left: state.map.pellets().len(),
+            remaining_ticks_dangerous,
+            policy: breakdown.policy,
+            proposals: &breakdown.proposals,
+            winner: breakdown.winner,
+            explanation: &breakdown.explanation,
+            decision,
+        };
+
+        let line = serde_json::to_string(&record).map_err(io::Error::other)?;
+        writeln!(self.out, "{}", line)?;
+        self.out.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ai::Bot;
+    use ai::config::BotConfig;
+    use protocol::Message;
+    use serde_json::Value;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use std::str::FromStr;
+
+    // Lets the test look at what was written after the bot is done with it
+    #[derive(Clone, Default)]
+    struct Shared(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn parse(me: u32, enemy: u32) -> GameState {
+        let message = format!(r#"{{"messagetype":"stateupdate","gamestate":{{"map":{{"content":["||||||||||","|___.____|","||||||||||"],"height":3,"width":10,"pelletsleft":1}},"you":{{"id":0,"x":{},"y":1}},"others":[{{"id":1,"x":{},"y":1}}]}}}}"#, me, enemy);
+        match Message::from_str(&message).unwrap() {
+            Message::Update { state } => state,
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn should_write_a_record_per_tick() {
+        let output = Shared::default();
+        let mut bot = Bot::with_config(&parse(1, 8), &BotConfig::default());
+        bot.set_tracer(Tracer::new(Box::new(output.clone())));
+        bot.determine_action(parse(1, 8));
+        bot.determine_action(parse(2, 7));
+        bot.determine_action(parse(3, 6));
+
+        let text = String::from_utf8(output.0.borrow().clone()).unwrap();
+        let records: Vec<Value> = text.lines().map(|l| serde_json::from_str(l).unwrap()).collect();
+        assert_eq!(3, records.len());
+
+        let last = &records[2];
+        assert_eq!(Some(3), last.pointer("/tick").and_then(|v| v.as_u64()));
+        assert_eq!(Some(3), last.pointer("/me/position/x").and_then(|v| v.as_u64()));
+        assert_eq!(Some(1), last.pointer("/enemies/0/id").and_then(|v| v.as_u64()));
+        assert_eq!(Some("Right"), last.pointer("/decision").and_then(|v| v.as_str()));
+
+        // The enemy is only classified once it has been seen moving
+        assert!(records[0].pointer("/enemies/0/classification").is_none());
+        assert!(last.pointer("/enemies/0/classification").is_some());
+
+        // With a single pellet left the endgame wins, but what the strategies after it wanted is there as well
+        let proposals = last.pointer("/proposals").and_then(|v| v.as_array()).unwrap();
+        let winner = last.pointer("/winner").and_then(|v| v.as_u64()).unwrap() as usize;
+        assert_eq!(Some("Endgame"), proposals[winner].pointer("/strategy").and_then(|s| s.as_str()));
+
+        // PickPellets says where it was going and how it meant to get there
+        let proposal = proposals
+            .iter()
+            .find(|p| p.pointer("/strategy").and_then(|s| s.as_str()) == Some("PickPellets"))
+            .unwrap();
+        assert_eq!(Some(4), proposal.pointer("/candidate/rationale/target/x").and_then(|v| v.as_u64()));
+        assert_eq!(Some(1), proposal.pointer("/candidate/rationale/path").and_then(|v| v.as_array()).map(|p| p.len()));
+    }
+}
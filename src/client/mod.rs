@@ -8,6 +8,8 @@ pub trait AIClient {
 
     fn wait_response(&mut self) -> bool;
     fn response(&self) -> Result<protocol::Message, protocol::Error>;
+    // The last message exactly as the server sent it
+    fn raw_response(&self) -> &str;
 
     fn send_action(&mut self, direction: &common::Direction);
 }
@@ -29,6 +29,10 @@ impl AIClient for TcpClient {
         protocol::Message::from_str(&self.last_response)
     }
 
+    fn raw_response(&self) -> &str {
+        &self.last_response
+    }
+
     fn send_action(&mut self, direction: &common::Direction) {
         self.stream.write_fmt(format_args!("{}\n", direction))
             .expect("Failed while sending action message");
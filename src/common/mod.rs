@@ -1,4 +1,3 @@
-use std::cmp;
 use std::collections::HashSet;
 use std::fmt;
 
@@ -6,7 +5,7 @@ pub mod rules;
 
 use traits::HasDimensions;
 
-#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Serialize)]
 pub enum Direction {
     Up,
     Down,
@@ -33,6 +32,15 @@ impl Direction {
         *self == Direction::Right && *other == Direction::Left
     }
 
+    pub fn opposite(&self) -> Direction {
+        match *self {
+            Direction::Up => Direction::Down,
+            Direction::Down => Direction::Up,
+            Direction::Left => Direction::Right,
+            Direction::Right => Direction::Left,
+        }
+    }
+
     pub fn hash_set_all() -> HashSet<Direction> {
         let mut set = HashSet::with_capacity(4);
         set.insert(Direction::Up);
@@ -43,7 +51,7 @@ impl Direction {
     }
 }
 
-#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Serialize)]
 pub struct Position {
     pub x: u32,
     pub y: u32,
@@ -57,6 +65,7 @@ impl Position {
         }
     }
 
+    #[cfg(test)]
     pub fn manhattan_distance_to<T: HasDimensions>(&self, other: &Position, limits: &T) -> u32 {
         let (x1, x2) = (self.x as i32, other.x as i32);
         let (y1, y2) = (self.y as i32, other.y as i32);
@@ -67,7 +76,7 @@ impl Position {
         let (w, h) = (limits.width() as i32 + 1, limits.height() as i32 + 1);
 
         // Manhattan distance for wrapping grid
-        (cmp::min((x1 - x2).abs(), h - 1 - (x1 - x2).abs()) + cmp::min((y1 - y2).abs(), w - 1 - (y1 - y2).abs())) as u32
+        ((x1 - x2).abs().min(h - 1 - (x1 - x2).abs()) + (y1 - y2).abs().min(w - 1 - (y1 - y2).abs())) as u32
     }
 
     // FIXME: This has to work with tunnels/wrapping
@@ -1 +1,17 @@
 pub const TICKS_DANGEROUS: u32 = 100;
+
+// Points awarded by the server
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Scores {
+    pub pellet: u32,
+    pub super_pellet: u32,
+    pub eat_player: u32,
+}
+
+// The server doesn't publish its scoring, so these are estimates from watching games. Strategies that depend on
+// them take pellet_score, super_pellet_score and eat_player_score params to correct them.
+pub const ESTIMATED_SCORES: Scores = Scores {
+    pellet: 1,
+    super_pellet: 5,
+    eat_player: 50,
+};
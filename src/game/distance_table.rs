@@ -0,0 +1,301 @@
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::rc::Rc;
+
+use common::{Direction, Position};
+use game::Map;
+use traits::HasDimensions;
+
+const NOT_WALKABLE: u16 = u16::MAX;
+const UNREACHABLE: u16 = u16::MAX;
+
+// The table takes count² cells, and indices have to fit in a u16 below the sentinels. Maps with more walkable tiles
+// than this search for distances when asked instead.
+const MAX_TILES: usize = 4096;
+
+const DIRECTIONS: [Direction; 4] = [Direction::Up, Direction::Down, Direction::Left, Direction::Right];
+
+// Shortest distance and first step between every pair of walkable tiles on a map. The map layout never
+// changes during a round, so this is calculated once and then queried in constant time.
+pub struct DistanceTable {
+    size: (u32, u32),
+    count: usize,
+
+    // Tile index in the map to index among walkable tiles
+    indices: Vec<u16>,
+
+    // count * count distances, row is origin and column is destination
+    distances: Vec<u16>,
+    // Direction of the first step in the same layout, packed as 2 bits each
+    next_hops: Vec<u8>,
+
+    // Only on maps with more than MAX_TILES walkable tiles, the table is left empty then
+    fallback: Option<Fallback>,
+}
+
+// Distance and first step from every tile towards one destination, None where it can't be reached from
+type Row = Vec<Option<(usize, Direction)>>;
+
+// Searches backwards from a destination the first time it's asked about, and keeps the most recent searches
+// within the memory the table would be allowed
+struct Fallback {
+    walkable: Vec<bool>,
+    rows: RefCell<VecDeque<(Position, Rc<Row>)>>,
+    max_rows: usize,
+}
+
+impl HasDimensions for DistanceTable {
+    fn width(&self) -> u32 {
+        self.size.0
+    }
+    fn height(&self) -> u32 {
+        self.size.1
+    }
+}
+
+impl DistanceTable {
+    pub fn from_map(map: &Map) -> DistanceTable {
+        DistanceTable::with_max_tiles(map, MAX_TILES)
+    }
+
+    fn with_max_tiles(map: &Map, max_tiles: usize) -> DistanceTable {
+        let walkable: Vec<bool> = map.tiles().iter().map(|t| t.is_walkable()).collect();
+        let walkable_count = walkable.iter().filter(|&&w| w).count();
+        if walkable_count > max_tiles {
+            warn!("{} walkable tiles is too many for the distance table, searching for distances as needed", walkable_count);
+            return DistanceTable {
+                size: (map.width(), map.height()),
+                count: 0,
+                indices: Vec::new(),
+                distances: Vec::new(),
+                next_hops: Vec::new(),
+                fallback: Some(Fallback {
+                    walkable,
+                    rows: RefCell::new(VecDeque::new()),
+                    max_rows: (max_tiles * max_tiles / walkable_count).max(1),
+                }),
+            };
+        }
+
+        let mut positions = Vec::new();
+        let mut indices = vec![NOT_WALKABLE; map.tiles().len()];
+        for y in 0..map.height() {
+            for x in 0..map.width() {
+                let position = Position::new(x, y);
+                if map.tile_at(&position).is_walkable() {
+                    indices[(y * map.width() + x) as usize] = positions.len() as u16;
+                    positions.push(position);
+                }
+            }
+        }
+
+        let count = positions.len();
+        let mut table = DistanceTable {
+            size: (map.width(), map.height()),
+            count,
+            indices,
+            distances: vec![UNREACHABLE; count * count],
+            next_hops: vec![0; (count * count).div_ceil(4)],
+            fallback: None,
+        };
+
+        for (destination, position) in positions.iter().enumerate() {
+            table.distances[destination * count + destination] = 0;
+            search_to(map, position, |adjacent, cost, direction| {
+                let cell = match table.index_of(adjacent) {
+                    Some(i) => i * count + destination,
+                    None => return false,
+                };
+                if table.distances[cell] != UNREACHABLE {
+                    return false;
+                }
+
+                table.distances[cell] = cost as u16;
+                table.set_next_hop(cell, direction);
+                true
+            });
+        }
+
+        table
+    }
+
+    pub fn distance(&self, from: &Position, to: &Position) -> Option<usize> {
+        if let Some(ref fallback) = self.fallback {
+            return self.tile_of(from).and_then(|f| fallback.row(self, to)[f].as_ref().map(|&(d, _)| d));
+        }
+
+        self.cell(from, to)
+            .map(|cell| self.distances[cell])
+            .and_then(|d| if d == UNREACHABLE { None } else { Some(d as usize) })
+    }
+
+    pub fn next_hop(&self, from: &Position, to: &Position) -> Option<Direction> {
+        if let Some(ref fallback) = self.fallback {
+            return match self.tile_of(from).and_then(|f| fallback.row(self, to)[f].clone()) {
+                Some((d, direction)) if d > 0 => Some(direction),
+                _ => None,
+            };
+        }
+
+        match self.cell(from, to) {
+            Some(cell) if self.distances[cell] != UNREACHABLE && self.distances[cell] > 0 => {
+                let bits = (self.next_hops[cell / 4] >> ((cell % 4) * 2)) & 0b11;
+                Some(DIRECTIONS[bits as usize].clone())
+            },
+            _ => None,
+        }
+    }
+
+    // Returns the path in the same order as the other pathfinding methods, the destination first
+    pub fn path(&self, from: &Position, to: &Position) -> Option<Vec<Position>> {
+        let mut path = match self.distance(from, to) {
+            Some(distance) => Vec::with_capacity(distance),
+            None => return None,
+        };
+
+        let mut current = from.clone();
+        while let Some(direction) = self.next_hop(&current, to) {
+            current = current.adjacent(self, &direction);
+            path.push(current.clone());
+        }
+
+        path.reverse();
+        Some(path)
+    }
+
+    fn tile_of(&self, position: &Position) -> Option<usize> {
+        if position.x >= self.size.0 || position.y >= self.size.1 {
+            return None;
+        }
+
+        Some((position.y * self.size.0 + position.x) as usize)
+    }
+
+    fn index_of(&self, position: &Position) -> Option<usize> {
+        match self.tile_of(position).map(|t| self.indices[t]) {
+            Some(NOT_WALKABLE) | None => None,
+            Some(i) => Some(i as usize),
+        }
+    }
+
+    fn cell(&self, from: &Position, to: &Position) -> Option<usize> {
+        match (self.index_of(from), self.index_of(to)) {
+            (Some(f), Some(t)) => Some(f * self.count + t),
+            _ => None,
+        }
+    }
+
+    fn set_next_hop(&mut self, cell: usize, direction: &Direction) {
+        let bits = DIRECTIONS.iter().position(|d| d == direction).unwrap() as u8;
+        self.next_hops[cell / 4] |= bits << ((cell % 4) * 2);
+    }
+}
+
+impl Fallback {
+    fn row(&self, table: &DistanceTable, destination: &Position) -> Rc<Row> {
+        if let Some((_, row)) = self.rows.borrow().iter().find(|(p, _)| p == destination) {
+            return row.clone();
+        }
+
+        let mut row: Row = vec![None; self.walkable.len()];
+        if let Some(tile) = table.tile_of(destination).filter(|&t| self.walkable[t]) {
+            row[tile] = Some((0, Direction::Up));
+            search_to(table, destination, |adjacent, cost, direction| {
+                let tile = table.tile_of(adjacent).unwrap();
+                if !self.walkable[tile] || row[tile].is_some() {
+                    return false;
+                }
+
+                row[tile] = Some((cost, direction.clone()));
+                true
+            });
+        }
+
+        let row = Rc::new(row);
+        let mut rows = self.rows.borrow_mut();
+        if rows.len() >= self.max_rows {
+            rows.pop_front();
+        }
+        rows.push_back((destination.clone(), row.clone()));
+        row
+    }
+}
+
+// BFS backwards from the destination, the direction we came from is the first step towards it. `visit` gets every
+// neighbour along with its distance and first step, and says whether it was new and should be searched from.
+fn search_to<T, F>(limits: &T, destination: &Position, mut visit: F)
+    where T: HasDimensions, F: FnMut(&Position, usize, &Direction) -> bool {
+    let mut frontier = VecDeque::new();
+    frontier.push_back((destination.clone(), 0));
+
+    while let Some((current, cost)) = frontier.pop_front() {
+        for direction in &DIRECTIONS {
+            let adjacent = current.adjacent(limits, direction);
+            if visit(&adjacent, cost + 1, &direction.opposite()) {
+                frontier.push_back((adjacent, cost + 1));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pathfinding;
+    use serde_json;
+
+    const DEFAULT_MAP: &str = r#"{"content":["||||||||||||||||||||||||||||","|............||............|","|.||||.|||||.||.|||||.||||.|","|o||||.|||||.||.|||||.||||o|","|.||||.|||||.||.|||||.||||.|","|....|................|....|","|.||||.||.||||||||.||.||||.|","|.||||.||.||||||||.||.||||.|","|....|.||....||....||.|....|","||||||.|||||_||_|||||.||||||","_____|.|||||_||_|||||.|_____","_____|.||__________||.|_____","_____|.||_|||--|||_||.|_____","||||||.||_|______|_||.||||||","______.___|______|___.______","||||||.||_|______|_||.||||||","_____|.||_|||--|||_||.|_____","_____|.||__________||.|_____","_____|.||_||||||||_||.|_____","||||||.||_||||||||_||.||||||","|....|.......||.......|....|","|.||||.|||||.||.|||||.||||.|","|.||||.|||||.||.|||||.||||.|","|o..||.......__.......||..o|","|||.||.||.||||||||.||.||.|||","|||.||.||.||||||||.||.||.|||","|......||....||....||......|","|.||||||||||.||.||||||||||.|","|.||||||||||.||.||||||||||.|","|..........................|","||||||||||||||||||||||||||||"],"height":31,"pelletsleft":238,"width":28}"#;
+
+    #[test]
+    fn distances_should_match_library_bfs() {
+        let map: Map = serde_json::from_str(DEFAULT_MAP).unwrap();
+        let table = DistanceTable::from_map(&map);
+
+        for origin in &[Position::new(3, 20), Position::new(6, 13), Position::new(13, 14)] {
+            for destination in map.super_pellets() {
+                let path = pathfinding::bfs(origin, |p| p.neighbours(&map).into_iter().filter(|x| map.tile_at(x).is_walkable()), |p| *p == destination);
+                assert_eq!(path.map(|p| p.len() - 1), table.distance(origin, &destination));
+            }
+        }
+
+        assert_eq!(Some(50), table.distance(&Position::new(3, 20), &Position::new(18, 1)));
+        assert_eq!(Some(0), table.distance(&Position::new(3, 20), &Position::new(3, 20)));
+        assert_eq!(None, table.distance(&Position::new(0, 0), &Position::new(3, 20)));
+    }
+
+    #[test]
+    fn can_follow_next_hops() {
+        let map: Map = serde_json::from_str(DEFAULT_MAP).unwrap();
+        let table = DistanceTable::from_map(&map);
+
+        // Through the tunnel
+        let origin = Position::new(6, 13);
+        let destination = Position::new(26, 14);
+
+        let path = table.path(&origin, &destination).unwrap();
+        assert_eq!(9, path.len());
+        assert_eq!(destination, path[0]);
+        assert!(path.iter().all(|p| map.tile_at(p).is_walkable()));
+        assert_eq!(Some(Direction::Down), table.next_hop(&origin, &destination));
+    }
+
+    #[test]
+    fn should_search_when_the_map_is_too_large_for_the_table() {
+        let map: Map = serde_json::from_str(DEFAULT_MAP).unwrap();
+        let table = DistanceTable::from_map(&map);
+        let fallback = DistanceTable::with_max_tiles(&map, 100);
+        assert!(fallback.fallback.is_some() && fallback.distances.is_empty());
+
+        // More destinations than the fallback keeps searches for
+        for origin in &[Position::new(3, 20), Position::new(6, 13), Position::new(0, 0)] {
+            for destination in map.pellets().iter().step_by(5) {
+                assert_eq!(table.distance(origin, destination), fallback.distance(origin, destination));
+                assert_eq!(table.next_hop(origin, destination), fallback.next_hop(origin, destination));
+            }
+        }
+
+        let (origin, destination) = (Position::new(6, 13), Position::new(26, 14));
+        assert_eq!(table.path(&origin, &destination), fallback.path(&origin, &destination));
+        assert!(fallback.fallback.unwrap().rows.borrow().len() <= 100 * 100 / map.tiles().iter().filter(|t| t.is_walkable()).count());
+    }
+}
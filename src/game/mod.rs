@@ -1,10 +1,19 @@
-use pathfinding;
-use std::collections::{HashSet, VecDeque};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
 
 use common::{Direction, Position};
 use protocol::json;
 use traits::HasDimensions;
 
+mod distance_table;
+mod path_query;
+pub mod sim;
+
+pub use self::distance_table::DistanceTable;
+pub use self::path_query::PathQuery;
+pub use self::sim::SimState;
+
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum TileType {
     Floor,
@@ -62,12 +71,6 @@ impl Map {
         ]
     }
 
-    pub fn points_in_path(&self, path: &Vec<Position>) -> usize {
-        path.iter()
-            .filter(|pos| self.tile_at(pos).is_pellet())
-            .count()
-    }
-
     pub fn pellets(&self) -> HashSet<Position> {
         self.tiles
             .iter()
@@ -88,6 +91,22 @@ impl Map {
             .collect()
     }
 
+    // Identifies the layout of the map, pellets being eaten doesn't change it but moving walls or doors does
+    pub fn fingerprint(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.width.hash(&mut hasher);
+        self.height().hash(&mut hasher);
+        for tile in &self.tiles {
+            let layout = match *tile {
+                TileType::Wall => 0u8,
+                TileType::Door => 1,
+                _ => 2,
+            };
+            layout.hash(&mut hasher);
+        }
+        hasher.finish()
+    }
+
     fn index_to_position(&self, index: usize) -> Position {
         let y = index as u32 / self.width;
         Position {
@@ -106,6 +125,21 @@ impl HasDimensions for Map {
     }
 }
 
+#[derive(Clone, Debug, PartialEq)]
+pub struct DeadEnd {
+    // The tile that has to be passed to get out, it's not part of the dead end itself
+    pub exit: Position,
+    // Number of steps required to reach the exit
+    pub depth: usize,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SpawnExit {
+    pub door: Position,
+    // The tile right outside the door
+    pub outside: Position,
+}
+
 #[derive(Default)]
 pub struct MapInformation {
     // This is essentially a combination of the three below, for convenience
@@ -113,9 +147,13 @@ pub struct MapInformation {
 
     intersections: HashSet<Position>,
     corners: HashSet<Position>,
-    dead_ends: HashSet<Position>,
+    dead_ends: HashMap<Position, DeadEnd>,
     tunnels: HashSet<Position>,
 
+    // The room behind the doors where players start, doors not included
+    spawn_area: HashSet<Position>,
+    spawn_exits: Vec<SpawnExit>,
+
     walkable_positions: HashSet<Position>,
     size: (u32, u32),
 }
@@ -167,43 +205,29 @@ impl MapInformation {
                 map_information.turning_points.insert(position.clone());
             }
             else if walkable_neighbours.len() == 1 {
-                map_information.dead_ends.insert(position.clone());
                 map_information.turning_points.insert(position.clone());
             }
         }
 
-        // Classify tiles in dead ends
-        let mut candidates = VecDeque::new();
-        candidates.append(&mut map_information.dead_ends.clone().into_iter().collect());
-        while let Some(c) = candidates.pop_front() {
-            let dead_neighbours: Vec<Position> = c.neighbours(map)
-                .into_iter()
-                .filter(|p| {
-                    map.tile_at(&p).is_walkable() &&
-                    !map_information.dead_ends.contains(p) &&
-                    p.neighbours(map).iter().filter(|pn| map.tile_at(&pn).is_walkable() && !map_information.dead_ends.contains(pn)).count() < 2
-                })
-                .collect();
+        // Classify tiles in dead ends, any area that can only be left through a single exit tile
+        map_information.dead_ends = classify_dead_ends(map, &map_information.walkable_positions);
 
-            for n in dead_neighbours {
-                map_information.dead_ends.insert(n.clone());
-                candidates.push_back(n);
-            }
-        }
+        let (spawn_area, spawn_exits) = classify_spawn(map, &map_information.walkable_positions);
+        map_information.spawn_area = spawn_area;
+        map_information.spawn_exits = spawn_exits;
 
         map_information
     }
 
-    pub fn intersections(&self) -> &HashSet<Position> {
-        // Return intersecions, tiles with >2 directions to go
-        // i.e. all tiles where a decision on where to go is needed
-        &self.intersections
-    }
-
     pub fn is_intersection(&self, position: &Position) -> bool {
         self.intersections.contains(position)
     }
 
+    // Tiles where there is more than one way to continue, or none at all
+    pub fn is_turning_point(&self, position: &Position) -> bool {
+        self.turning_points.contains(position)
+    }
+
     pub fn walkable_positions(&self) -> &HashSet<Position> {
         &self.walkable_positions
     }
@@ -229,22 +253,247 @@ impl MapInformation {
             .collect()
     }
 
-    pub fn dead_ends(&self) -> &HashSet<Position> {
+    pub fn dead_ends(&self) -> &HashMap<Position, DeadEnd> {
         &self.dead_ends
     }
 
     pub fn is_dead_end(&self, position: &Position) -> bool {
-        self.dead_ends.contains(position)
+        self.dead_ends.contains_key(position)
+    }
+
+    pub fn dead_end(&self, position: &Position) -> Option<&DeadEnd> {
+        self.dead_ends.get(position)
+    }
+
+    pub fn is_in_spawn(&self, position: &Position) -> bool {
+        self.spawn_area.contains(position)
+    }
+
+    pub fn spawn_exits(&self) -> &[SpawnExit] {
+        &self.spawn_exits
     }
 
     pub fn path_to_dead_end_exit(&self, position: &Position) -> Option<Vec<Position>> {
-        pathfinding::bfs(position, |p| p.neighbours(self).into_iter().filter(|c| self.walkable_positions.contains(&c)), |p| !self.dead_ends.contains(&p))
-            .and_then(|path| Some(path
-                .into_iter()
-                .skip(1)
-                .rev()
-                .collect()))
+        let exit = match self.dead_ends.get(position) {
+            Some(dead_end) => &dead_end.exit,
+            None => return None,
+        };
+
+        // Stay inside the area, it might contain loops that would otherwise lead us out some other way
+        PathQuery::on(self, position)
+            .to(exit)
+            .blocked_by(move |p| *p != *exit && self.dead_ends.get(p).map(|d| d.exit != *exit).unwrap_or(true))
+            .find()
+    }
+}
+
+// The spawn area is what's cut off from the rest of the map by the doors. Whatever is left once the doors are
+// removed falls apart in parts, every part next to a door except the largest one is part of the spawn area.
+fn classify_spawn(map: &Map, walkable_positions: &HashSet<Position>) -> (HashSet<Position>, Vec<SpawnExit>) {
+    let mut doors: Vec<Position> = walkable_positions
+        .iter()
+        .filter(|p| map.tile_at(p) == TileType::Door)
+        .cloned()
+        .collect();
+    doors.sort_by_key(|p| (p.y, p.x));
+
+    // Flood fill the parts next to the doors
+    let mut parts: Vec<HashSet<Position>> = Vec::new();
+    for door in &doors {
+        for start in door.neighbours(map) {
+            if !walkable_positions.contains(&start) || map.tile_at(&start) == TileType::Door || parts.iter().any(|p| p.contains(&start)) {
+                continue;
+            }
+
+            let mut part = HashSet::new();
+            let mut frontier = vec![start.clone()];
+            part.insert(start);
+            while let Some(current) = frontier.pop() {
+                for adjacent in current.neighbours(map) {
+                    if walkable_positions.contains(&adjacent) && map.tile_at(&adjacent) != TileType::Door && part.insert(adjacent.clone()) {
+                        frontier.push(adjacent);
+                    }
+                }
+            }
+
+            parts.push(part);
+        }
+    }
+
+    let largest = match (0..parts.len()).max_by_key(|&i| parts[i].len()) {
+        Some(i) => parts.swap_remove(i),
+        None => return (HashSet::new(), Vec::new()),
+    };
+
+    let spawn_area: HashSet<Position> = parts.into_iter().flat_map(|p| p.into_iter()).collect();
+    let spawn_exits = doors
+        .iter()
+        .filter(|door| door.neighbours(map).iter().any(|n| spawn_area.contains(n)))
+        .filter_map(|door| door.neighbours(map).into_iter().find(|n| largest.contains(n)).map(|outside| SpawnExit {
+            door: door.clone(),
+            outside,
+        }))
+        .collect();
+
+    (spawn_area, spawn_exits)
+}
+
+// Finds all areas that can only be reached through a single tile, i.e. the articulation points of the
+// walkable graph. Removing such a tile splits the map in parts, where every part but the largest one is
+// a dead end. Nested areas are all assigned to the outermost (largest) area they belong to.
+fn classify_dead_ends(map: &Map, walkable_positions: &HashSet<Position>) -> HashMap<Position, DeadEnd> {
+    let mut positions: Vec<Position> = walkable_positions.iter().cloned().collect();
+    positions.sort_by_key(|p| (p.y, p.x));
+
+    let indices: HashMap<Position, usize> = positions
+        .iter()
+        .enumerate()
+        .map(|(i, p)| (p.clone(), i))
+        .collect();
+
+    let adjacency: Vec<Vec<usize>> = positions
+        .iter()
+        .enumerate()
+        .map(|(i, p)| {
+            let mut adjacent: Vec<usize> = p.neighbours(map)
+                .iter()
+                .filter_map(|n| indices.get(n).cloned())
+                .filter(|&n| n != i)
+                .collect();
+            adjacent.sort();
+            adjacent.dedup();
+            adjacent
+        })
+        .collect();
+
+    // Iterative DFS (Tarjan) to get discovery order, low links and subtree sizes
+    const UNVISITED: usize = usize::MAX;
+    let count = positions.len();
+    let mut discovered = vec![UNVISITED; count];
+    let mut low = vec![0; count];
+    let mut parent = vec![UNVISITED; count];
+    let mut subtree_size = vec![1; count];
+    let mut order: Vec<usize> = Vec::with_capacity(count);
+    let mut components: Vec<(usize, usize)> = Vec::new(); // (first index in DFS order, size)
+
+    for root in 0..count {
+        if discovered[root] != UNVISITED {
+            continue;
+        }
+
+        let first = order.len();
+        discovered[root] = order.len();
+        low[root] = order.len();
+        order.push(root);
+
+        let mut stack = vec![(root, 0)];
+        while let Some((node, next_edge)) = stack.pop() {
+            if next_edge < adjacency[node].len() {
+                stack.push((node, next_edge + 1));
+
+                let adjacent = adjacency[node][next_edge];
+                if discovered[adjacent] == UNVISITED {
+                    parent[adjacent] = node;
+                    discovered[adjacent] = order.len();
+                    low[adjacent] = order.len();
+                    order.push(adjacent);
+                    stack.push((adjacent, 0));
+                }
+                else if adjacent != parent[node] {
+                    low[node] = low[node].min(discovered[adjacent]);
+                }
+            }
+            else if parent[node] != UNVISITED {
+                let p = parent[node];
+                low[p] = low[p].min(low[node]);
+                subtree_size[p] += subtree_size[node];
+            }
+        }
+
+        components.push((first, order.len() - first));
+    }
+
+    // For every tile, the size of the largest dead end it belongs to and the exit of it
+    let mut assigned: Vec<Option<(usize, usize)>> = vec![None; count];
+
+    // Tiles that are not connected to the largest part of the map can't be reached, so ignore them
+    let &(first, size) = match components.iter().max_by_key(|&&(_, size)| size) {
+        Some(component) => component,
+        None => return HashMap::new(),
+    };
+
+    for &node in &order[first..first + size] {
+        let is_root = parent[node] == UNVISITED;
+
+        // Parts of the map that get cut off from the rest if this tile is removed, as ranges in DFS order
+        let separated: Vec<(usize, usize)> = adjacency[node]
+            .iter()
+            .filter(|&&c| parent[c] == node && (is_root || low[c] >= discovered[node]))
+            .map(|&c| (discovered[c], subtree_size[c]))
+            .collect();
+
+        let remainder = size - 1 - separated.iter().map(|&(_, s)| s).sum::<usize>();
+        let parts = separated.len() + if remainder > 0 { 1 } else { 0 };
+        if parts < 2 {
+            continue;
+        }
+
+        let largest = separated
+            .iter()
+            .map(|&(_, s)| s)
+            .chain(Some(remainder))
+            .max()
+            .unwrap();
+
+        let mut assign = |tile: usize, area_size: usize| {
+            if assigned[tile].map(|(s, _)| area_size > s).unwrap_or(true) {
+                assigned[tile] = Some((area_size, node));
+            }
+        };
+
+        for &(start, s) in separated.iter().filter(|&&(_, s)| s < largest) {
+            for &tile in &order[start..start + s] {
+                assign(tile, s);
+            }
+        }
+
+        if remainder > 0 && remainder < largest {
+            for &tile in &order[first..first + size] {
+                let is_separated = separated.iter().any(|&(start, s)| discovered[tile] >= start && discovered[tile] < start + s);
+                if tile != node && !is_separated {
+                    assign(tile, remainder);
+                }
+            }
+        }
     }
+
+    // Walk each dead end outwards from its exit to get the depth of every tile
+    let mut dead_ends = HashMap::new();
+    let exits: HashSet<usize> = assigned.iter().filter_map(|a| a.map(|(_, exit)| exit)).collect();
+    for exit in exits {
+        let mut frontier = VecDeque::new();
+        frontier.push_back((exit, 0));
+
+        let mut visited = HashSet::new();
+        visited.insert(exit);
+
+        while let Some((current, depth)) = frontier.pop_front() {
+            for &adjacent in &adjacency[current] {
+                if visited.contains(&adjacent) || assigned[adjacent].map(|(_, e)| e) != Some(exit) {
+                    continue;
+                }
+
+                visited.insert(adjacent);
+                dead_ends.insert(positions[adjacent].clone(), DeadEnd {
+                    exit: positions[exit].clone(),
+                    depth: depth + 1,
+                });
+                frontier.push_back((adjacent, depth + 1));
+            }
+        }
+    }
+
+    dead_ends
 }
 
 #[cfg(test)]
@@ -424,11 +673,9 @@ mod tests {
         let info = MapInformation::from_map(&serde_json::from_str::<Map>(DEFAULT).unwrap());
         assert_eq!(58, info.dead_ends().len());
 
-        // 4 tiles (unreachable)
+        // The remaining maps have no dead ends, MSPACMAN1 has an unreachable pocket but it has no exit
         let info = MapInformation::from_map(&serde_json::from_str::<Map>(MSPACMAN1).unwrap());
-        assert_eq!(4, info.dead_ends().len());
-
-        // The remaining maps have no dead ends
+        assert_eq!(0, info.dead_ends().len());
         let info = MapInformation::from_map(&serde_json::from_str::<Map>(MSPACMAN2).unwrap());
         assert_eq!(0, info.dead_ends().len());
         let info = MapInformation::from_map(&serde_json::from_str::<Map>(MSPACMAN3).unwrap());
@@ -439,6 +686,25 @@ mod tests {
         assert_eq!(0, info.dead_ends().len());
     }
 
+    #[test]
+    fn can_find_spawn_area() {
+        let info = MapInformation::from_map(&serde_json::from_str::<Map>(DEFAULT).unwrap());
+        assert!(info.is_in_spawn(&Position::new(13, 14)));
+        assert!(!info.is_in_spawn(&Position::new(13, 12)));
+        assert!(!info.is_in_spawn(&Position::new(13, 11)));
+
+        let outside: Vec<Position> = info.spawn_exits().iter().map(|e| e.outside.clone()).collect();
+        assert_eq!(vec![Position::new(13, 11), Position::new(14, 11), Position::new(13, 17), Position::new(14, 17)], outside);
+    }
+
+    #[test]
+    fn can_tell_map_layouts_apart() {
+        let map = |json: &str| serde_json::from_str::<Map>(json).unwrap();
+        assert_eq!(map(DEFAULT).fingerprint(), map(&DEFAULT.replacen(".", "_", 10)).fingerprint());
+        assert!(map(DEFAULT).fingerprint() != map(PACMAN).fingerprint());
+        assert!(map(MSPACMAN1).fingerprint() != map(MSPACMAN2).fingerprint());
+    }
+
     #[test]
     fn can_find_dead_end_exit() {
         let info = MapInformation::from_map(&serde_json::from_str::<Map>(DEFAULT).unwrap());
@@ -451,6 +717,43 @@ mod tests {
         let path = info.path_to_dead_end_exit(&current).unwrap();
         assert_eq!(11, path.len());
         assert_eq!(exit, path[0]);
+        assert_eq!(11, info.dead_end(&current).unwrap().depth);
+    }
+
+    #[test]
+    fn can_classify_loops_behind_single_exit_as_dead_ends() {
+        const POCKET: &'static str = r#"
+{
+    "content": [
+        "|||||||||",
+        "|_______|",
+        "|_|||||_|",
+        "|_______|",
+        "||||_||||",
+        "||||_||||",
+        "|||___|||",
+        "|||_|_|||",
+        "|||___|||",
+        "|||||||||"
+    ],
+    "height": 10,
+    "pelletsleft": 0,
+    "width": 9
+}"#;
+        let info = MapInformation::from_map(&serde_json::from_str::<Map>(POCKET).unwrap());
+
+        // The corridor and the loop at the end of it
+        assert_eq!(10, info.dead_ends().len());
+        assert!(!info.is_dead_end(&Position::new(4, 3)));
+
+        let current = Position::new(4, 8);
+        let dead_end = info.dead_end(&current).unwrap();
+        assert_eq!(Position::new(4, 3), dead_end.exit);
+        assert_eq!(7, dead_end.depth);
+
+        let path = info.path_to_dead_end_exit(&current).unwrap();
+        assert_eq!(7, path.len());
+        assert_eq!(Position::new(4, 3), path[0]);
     }
 }
 
@@ -0,0 +1,348 @@
+use std::cmp::{self, Reverse};
+use std::collections::{BinaryHeap, HashMap, HashSet, VecDeque};
+
+use common::Position;
+use game::Map;
+use protocol::Player;
+use traits::{HasDimensions, HasPosition};
+
+// Builder for the different kinds of path searches, so that the neighbour and goal closures don't have to be
+// written over and over again. Breadth-first search, Dijkstra or A* is picked depending on what is asked for.
+pub struct PathQuery<'a, T: 'a + HasDimensions> {
+    limits: &'a T,
+    origin: Position,
+    goal: Goal<'a>,
+
+    walkable: PositionFilter<'a>,
+    blocked: Vec<PositionFilter<'a>>,
+    extra_cost: Option<PositionCost<'a>>,
+    max_depth: Option<usize>,
+    timing: Option<Timing<'a>>,
+}
+
+type PositionFilter<'a> = Box<dyn Fn(&Position) -> bool + 'a>;
+type PositionCost<'a> = Box<dyn Fn(&Position) -> Option<usize> + 'a>;
+type StepFilter<'a> = Box<dyn Fn(&Position, &Position, usize) -> bool + 'a>;
+
+enum Goal<'a> {
+    Position(Position),
+    Set(HashSet<Position>),
+    Predicate(PositionFilter<'a>),
+}
+
+struct Timing<'a> {
+    max_ticks: usize,
+    // Whether a step from one position to the next can be taken, starting at the given tick
+    can_step: StepFilter<'a>,
+}
+
+impl<'a> PathQuery<'a, Map> {
+    pub fn new(map: &'a Map, origin: &Position) -> PathQuery<'a, Map> {
+        let mut query = PathQuery::on(map, origin);
+        query.walkable = Box::new(move |p| map.tile_at(p).is_walkable());
+        query
+    }
+}
+
+impl<'a, T: HasDimensions> PathQuery<'a, T> {
+    // Without a map there are no walls, use `blocked_by` to say what can't be walked on
+    pub fn on(limits: &'a T, origin: &Position) -> PathQuery<'a, T> {
+        PathQuery {
+            limits,
+            origin: origin.clone(),
+            goal: Goal::Set(HashSet::new()),
+            walkable: Box::new(|_| true),
+            blocked: Vec::new(),
+            extra_cost: None,
+            max_depth: None,
+            timing: None,
+        }
+    }
+
+    pub fn to(mut self, destination: &Position) -> Self {
+        self.goal = Goal::Position(destination.clone());
+        self
+    }
+
+    // Whichever of the destinations is the closest
+    pub fn any_of(mut self, destinations: HashSet<Position>) -> Self {
+        self.goal = Goal::Set(destinations);
+        self
+    }
+
+    // The closest position the predicate is true for
+    pub fn nearest<F>(mut self, is_goal: F) -> Self
+        where F: Fn(&Position) -> bool + 'a {
+        self.goal = Goal::Predicate(Box::new(is_goal));
+        self
+    }
+
+    // Positions that may not be entered, can be called multiple times
+    pub fn blocked_by<F>(mut self, is_blocked: F) -> Self
+        where F: Fn(&Position) -> bool + 'a {
+        self.blocked.push(Box::new(is_blocked));
+        self
+    }
+
+    pub fn avoiding(self, enemies: &'a [Player]) -> Self {
+        self.blocked_by(move |p| enemies.iter().any(|e| e.position() == *p))
+    }
+
+    // Cost of entering a position on top of the one tick every step takes, None if it should not be entered
+    pub fn extra_cost<F>(mut self, cost: F) -> Self
+        where F: Fn(&Position) -> Option<usize> + 'a {
+        self.extra_cost = Some(Box::new(cost));
+        self
+    }
+
+    // Maximum number of steps in the path
+    pub fn max_depth(mut self, depth: usize) -> Self {
+        self.max_depth = Some(depth);
+        self
+    }
+
+    // Searches over both position and time for up to the given number of ticks, asking about every step with the
+    // tick it starts on. Walking in to a wall is how we wait, which shows up as the same position twice in a row in
+    // the path. Extra costs are not taken in to account.
+    pub fn timed<F>(mut self, max_ticks: usize, can_step: F) -> Self
+        where F: Fn(&Position, &Position, usize) -> bool + 'a {
+        self.timing = Some(Timing {
+            max_ticks,
+            can_step: Box::new(can_step),
+        });
+        self
+    }
+
+    // Returns the path with the destination first and without the origin, like the rest of the pathfinding
+    pub fn find(&self) -> Option<Vec<Position>> {
+        match (&self.goal, &self.extra_cost, &self.timing) {
+            (_, _, Some(timing)) => self.timed_breadth_first(timing),
+            (Goal::Position(destination), _, _) => self.best_first(|p| self.wrapped_distance(p, destination)),
+            (_, Some(_), _) => self.best_first(|_| 0),
+            (_, None, _) => self.breadth_first(),
+        }
+    }
+
+    fn is_goal(&self, position: &Position) -> bool {
+        match self.goal {
+            Goal::Position(ref destination) => *position == *destination,
+            Goal::Set(ref destinations) => destinations.contains(position),
+            Goal::Predicate(ref is_goal) => is_goal(position),
+        }
+    }
+
+    fn can_enter(&self, position: &Position) -> bool {
+        (self.walkable)(position) && !self.blocked.iter().any(|is_blocked| is_blocked(position))
+    }
+
+    fn is_too_deep(&self, depth: usize) -> bool {
+        self.max_depth.map(|max| depth >= max).unwrap_or(false)
+    }
+
+    fn breadth_first(&self) -> Option<Vec<Position>> {
+        let mut parents: HashMap<Position, Position> = HashMap::new();
+        let mut frontier = VecDeque::new();
+        frontier.push_back((self.origin.clone(), 0));
+
+        while let Some((current, depth)) = frontier.pop_front() {
+            if self.is_goal(&current) {
+                return Some(backtrack(&parents, current));
+            }
+
+            if self.is_too_deep(depth) {
+                continue;
+            }
+
+            for adjacent in current.neighbours(self.limits) {
+                if adjacent == self.origin || parents.contains_key(&adjacent) || !self.can_enter(&adjacent) {
+                    continue;
+                }
+
+                parents.insert(adjacent.clone(), current.clone());
+                frontier.push_back((adjacent, depth + 1));
+            }
+        }
+
+        None
+    }
+
+    // Every step takes a tick, so the first time the goal comes up is also the earliest we can be there
+    fn timed_breadth_first(&self, timing: &Timing) -> Option<Vec<Position>> {
+        let origin = (self.origin.clone(), 0);
+        let mut parents: HashMap<(Position, usize), (Position, usize)> = HashMap::new();
+        let mut frontier = VecDeque::new();
+        frontier.push_back(origin.clone());
+
+        while let Some((current, tick)) = frontier.pop_front() {
+            if self.is_goal(&current) {
+                let path = backtrack(&parents, (current, tick));
+                return Some(path.into_iter().map(|(p, _)| p).collect());
+            }
+
+            if tick >= timing.max_ticks || self.is_too_deep(tick) {
+                continue;
+            }
+
+            for adjacent in current.neighbours(self.limits) {
+                let next = if (self.walkable)(&adjacent) { adjacent } else { current.clone() };
+                if self.blocked.iter().any(|is_blocked| is_blocked(&next)) || !(timing.can_step)(&current, &next, tick) {
+                    continue;
+                }
+
+                let node = (next, tick + 1);
+                if node == origin || parents.contains_key(&node) {
+                    continue;
+                }
+
+                parents.insert(node.clone(), (current.clone(), tick));
+                frontier.push_back(node);
+            }
+        }
+
+        None
+    }
+
+    // Dijkstra, or A* when given a heuristic that isn't zero
+    fn best_first<H>(&self, heuristic: H) -> Option<Vec<Position>>
+        where H: Fn(&Position) -> usize {
+        let mut parents: HashMap<Position, Position> = HashMap::new();
+        let mut costs: HashMap<Position, usize> = HashMap::new();
+        let mut positions = vec![self.origin.clone()];
+        let mut frontier = BinaryHeap::new();
+
+        costs.insert(self.origin.clone(), 0);
+        frontier.push(Reverse((heuristic(&self.origin), 0, 0, 0)));
+
+        while let Some(Reverse((_, cost, depth, index))) = frontier.pop() {
+            let current = positions[index].clone();
+            if costs.get(&current).map(|&c| c < cost).unwrap_or(false) {
+                continue;
+            }
+
+            if self.is_goal(&current) {
+                return Some(backtrack(&parents, current));
+            }
+
+            if self.is_too_deep(depth) {
+                continue;
+            }
+
+            for adjacent in current.neighbours(self.limits) {
+                if adjacent == self.origin || !self.can_enter(&adjacent) {
+                    continue;
+                }
+
+                let extra = match self.extra_cost {
+                    Some(ref extra_cost) => match extra_cost(&adjacent) {
+                        Some(c) => c,
+                        None => continue,
+                    },
+                    None => 0,
+                };
+
+                let adjacent_cost = cost + 1 + extra;
+                if costs.get(&adjacent).map(|&c| c <= adjacent_cost).unwrap_or(false) {
+                    continue;
+                }
+
+                costs.insert(adjacent.clone(), adjacent_cost);
+                parents.insert(adjacent.clone(), current.clone());
+                frontier.push(Reverse((adjacent_cost + heuristic(&adjacent), adjacent_cost, depth + 1, positions.len())));
+                positions.push(adjacent);
+            }
+        }
+
+        None
+    }
+
+    // Never overestimates the number of steps, even when walking through the edges of the map
+    fn wrapped_distance(&self, from: &Position, to: &Position) -> usize {
+        let dx = (from.x as i64 - to.x as i64).unsigned_abs() as u32;
+        let dy = (from.y as i64 - to.y as i64).unsigned_abs() as u32;
+        (cmp::min(dx, self.limits.width() - dx) + cmp::min(dy, self.limits.height() - dy)) as usize
+    }
+}
+
+fn backtrack<N: Clone + Eq + ::std::hash::Hash>(parents: &HashMap<N, N>, destination: N) -> Vec<N> {
+    let mut path = vec![destination];
+    while let Some(parent) = parents.get(path.last().unwrap()) {
+        path.push(parent.clone());
+    }
+
+    // The origin is the last one, we don't need it
+    path.pop();
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pathfinding::astar;
+    use serde_json;
+
+    // A loop with a short corridor off the left side
+    const MAP: &str = r#"{"content":["|||||||||","|_______|","|_|||||_|","|_______|","|||||||||"],"height":5,"pelletsleft":0,"width":9}"#;
+
+    #[test]
+    fn should_search_the_way_that_was_asked_for() {
+        let map: Map = serde_json::from_str(MAP).unwrap();
+        let origin = Position::new(1, 1);
+        let far = Position::new(7, 3);
+
+        // Unit cost to a single position is A*, and should agree with the library
+        let path = PathQuery::new(&map, &origin).to(&far).find().unwrap();
+        let library = astar(&origin, |p| p.neighbours(&map).into_iter().filter(|x| map.tile_at(x).is_walkable()).map(|x| (x, 1)), |p| p.manhattan_distance_to(&far, &map) as usize, |p| *p == far).unwrap();
+        assert_eq!(library.1, path.len());
+        assert_eq!(far, path[0]);
+
+        let path = PathQuery::new(&map, &origin).nearest(|p| p.y == 3).find().unwrap();
+        assert_eq!(vec![Position::new(1, 3), Position::new(1, 2)], path);
+
+        // Going the other way around when the short way is blocked
+        let path = PathQuery::new(&map, &origin).nearest(|p| p.y == 3).blocked_by(|p| p.x == 1 && p.y == 2).find().unwrap();
+        assert_eq!(Position::new(7, 3), path[0]);
+
+        assert!(PathQuery::new(&map, &origin).to(&Position::new(0, 0)).find().is_none());
+    }
+
+    #[test]
+    fn should_pick_closest_goal_within_depth() {
+        let map: Map = serde_json::from_str(MAP).unwrap();
+        let origin = Position::new(1, 1);
+        let near = Position::new(1, 3);
+        let far = Position::new(7, 3);
+
+        let goals: HashSet<Position> = vec![near.clone(), far.clone()].into_iter().collect();
+        let path = PathQuery::new(&map, &origin).any_of(goals.clone()).find().unwrap();
+        assert_eq!(near, path[0]);
+
+        // The far one is 8 steps away whichever way we go
+        assert!(PathQuery::new(&map, &origin).nearest(|p| *p == far).max_depth(7).find().is_none());
+        assert_eq!(8, PathQuery::new(&map, &origin).nearest(|p| *p == far).max_depth(8).find().unwrap().len());
+
+        // Cutting off the way to the near one, with and without costs
+        let blocked = |p: &Position| *p == Position::new(1, 2);
+        assert!(PathQuery::new(&map, &origin).any_of(goals.clone()).blocked_by(blocked).max_depth(7).find().is_none());
+        let path = PathQuery::new(&map, &origin).any_of(goals).blocked_by(blocked).extra_cost(|_| Some(0)).max_depth(8).find().unwrap();
+        assert_eq!(far, path[0]);
+        assert!(PathQuery::new(&map, &origin).to(&far).max_depth(7).find().is_none());
+    }
+
+    #[test]
+    fn timed_search_should_wait_when_it_has_to() {
+        let map: Map = serde_json::from_str(MAP).unwrap();
+        let origin = Position::new(1, 1);
+        let destination = Position::new(3, 1);
+
+        // The tile in between is taken for the first tick
+        let path = PathQuery::new(&map, &origin)
+            .to(&destination)
+            .timed(10, |_, to, tick| !(*to == Position::new(2, 1) && tick == 0))
+            .find()
+            .unwrap();
+        assert_eq!(vec![destination.clone(), Position::new(2, 1), origin.clone()], path);
+
+        let path = PathQuery::new(&map, &origin).to(&destination).timed(1, |_, _, _| true).find();
+        assert!(path.is_none());
+    }
+}
@@ -0,0 +1,432 @@
+use common::{Direction, Position, rules};
+use game::Map;
+use protocol::GameState;
+use traits::{HasDimensions, HasPosition};
+
+const DIRECTIONS: [Direction; 4] = [Direction::Up, Direction::Down, Direction::Left, Direction::Right];
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SimPlayer {
+    pub id: u32,
+    pub position: Position,
+    pub score: u32,
+    // Ticks left until the player can be eaten again
+    pub dangerous_ticks: u32,
+    pub alive: bool,
+    pub last_direction: Option<Direction>,
+}
+
+impl SimPlayer {
+    pub fn is_dangerous(&self) -> bool {
+        self.dangerous_ticks > 0
+    }
+}
+
+// What happened during a tick, players are indices in to `SimState::players`
+#[derive(Clone, Debug, PartialEq)]
+pub enum Event {
+    Pellet { player: usize },
+    SuperPellet { player: usize },
+    Kill { eater: usize, eaten: usize },
+}
+
+// Everything needed to take back a tick
+#[derive(Clone)]
+struct Undo {
+    players: Vec<SimPlayer>,
+    eaten_pellets: Vec<usize>,
+}
+
+// Forward model of the game, for trying out moves without waiting for the server. The map layout never changes
+// so only the things that do are kept here, with the remaining pellets as one bit per tile.
+#[derive(Clone)]
+pub struct SimState<'a> {
+    map: &'a Map,
+    pellets: Vec<u64>,
+
+    // We're always the first player
+    pub players: Vec<SimPlayer>,
+    pub tick: u32,
+    scores: rules::Scores,
+
+    history: Vec<Undo>,
+}
+
+impl<'a> SimState<'a> {
+    // The server doesn't tell for how long enemies will stay dangerous, so assume the worst
+    pub fn from_game_state(state: &'a GameState, my_dangerous_ticks: u32) -> SimState<'a> {
+        let player = |id: u32, position: Position, score: u32, dangerous_ticks: u32| SimPlayer {
+            id,
+            position,
+            score,
+            dangerous_ticks,
+            alive: true,
+            last_direction: None,
+        };
+
+        let mut players = vec![player(state.me.id, state.me.position(), state.me.score, my_dangerous_ticks)];
+        players.extend(state.enemies
+            .iter()
+            .map(|e| player(e.id, e.position(), e.score, if e.is_dangerous { rules::TICKS_DANGEROUS } else { 0 })));
+
+        let tiles = state.map.tiles();
+        let mut pellets = vec![0; tiles.len().div_ceil(64)];
+        for (i, _) in tiles.iter().enumerate().filter(|&(_, t)| t.is_pellet()) {
+            pellets[i / 64] |= 1 << (i % 64);
+        }
+
+        SimState {
+            map: &state.map,
+            pellets,
+            players,
+            tick: 0,
+            scores: rules::ESTIMATED_SCORES,
+            history: Vec::new(),
+        }
+    }
+
+    pub fn with_scores(mut self, scores: rules::Scores) -> SimState<'a> {
+        self.scores = scores;
+        self
+    }
+
+    // Fills in which way everyone was going, based on where they were the tick before
+    pub fn infer_last_directions(&mut self, previous: &GameState) {
+        let map = self.map;
+        for player in &mut self.players {
+            let previous_position = if player.id == previous.me.id {
+                Some(previous.me.position())
+            }
+            else {
+                previous.enemies.iter().find(|e| e.id == player.id).map(|e| e.position())
+            };
+
+            // Anything but a single step, like respawning, doesn't say anything about the direction
+            player.last_direction = previous_position
+                .filter(|p| p.neighbours(map).contains(&player.position))
+                .and_then(|p| p.direction_to(map, &player.position));
+        }
+    }
+
+    pub fn map(&self) -> &'a Map {
+        self.map
+    }
+
+    pub fn scores(&self) -> &rules::Scores {
+        &self.scores
+    }
+
+    pub fn me(&self) -> &SimPlayer {
+        &self.players[0]
+    }
+
+    fn index_of(&self, position: &Position) -> usize {
+        (position.y * self.map.width() + position.x) as usize
+    }
+
+    fn position_of(&self, index: usize) -> Position {
+        let index = index as u32;
+        Position::new(index % self.map.width(), index / self.map.width())
+    }
+
+    pub fn has_pellet(&self, position: &Position) -> bool {
+        let index = self.index_of(position);
+        self.pellets[index / 64] & (1 << (index % 64)) != 0
+    }
+
+    pub fn pellets<'b>(&'b self) -> impl Iterator<Item = Position> + 'b {
+        self.pellets
+            .iter()
+            .enumerate()
+            .flat_map(|(word, &bits)| (0..64).filter(move |b| bits & (1 << b) != 0).map(move |b| word * 64 + b))
+            .map(move |i| self.position_of(i))
+    }
+
+    // Directions that don't walk in to a wall
+    pub fn legal_moves(&self, player: usize) -> Vec<Direction> {
+        let position = &self.players[player].position;
+        DIRECTIONS
+            .iter()
+            .filter(|d| self.map.tile_at(&position.adjacent(self.map, d)).is_walkable())
+            .cloned()
+            .collect()
+    }
+
+    // Moves every player at once, one direction per player in the same order as `players`
+    pub fn apply(&mut self, moves: &[Direction]) -> Vec<Event> {
+        debug_assert_eq!(self.players.len(), moves.len());
+        self.tick += 1;
+
+        let mut undo = Undo {
+            players: self.players.clone(),
+            eaten_pellets: Vec::new(),
+        };
+        let mut events = Vec::new();
+
+        for (i, direction) in moves.iter().enumerate() {
+            if !self.players[i].alive {
+                continue;
+            }
+
+            let map = self.map;
+            let player = &mut self.players[i];
+            player.dangerous_ticks = player.dangerous_ticks.saturating_sub(1);
+
+            // Walking in to a wall means standing still
+            let next = player.position.adjacent(map, direction);
+            if map.tile_at(&next).is_walkable() {
+                player.position = next;
+            }
+            player.last_direction = Some(direction.clone());
+
+            let index = (player.position.y * map.width() + player.position.x) as usize;
+            if self.pellets[index / 64] & (1 << (index % 64)) == 0 {
+                continue;
+            }
+
+            self.pellets[index / 64] &= !(1 << (index % 64));
+            undo.eaten_pellets.push(index);
+
+            if map.tiles()[index].is_super_pellet() {
+                player.score += self.scores.super_pellet;
+                player.dangerous_ticks = rules::TICKS_DANGEROUS;
+                events.push(Event::SuperPellet { player: i });
+            }
+            else {
+                player.score += self.scores.pellet;
+                events.push(Event::Pellet { player: i });
+            }
+        }
+
+        // Players that end up on the same tile, or walk through each other, collide
+        for i in 0..self.players.len() {
+            for j in (i + 1)..self.players.len() {
+                let (a, b) = (&self.players[i], &self.players[j]);
+                if !a.alive || !b.alive {
+                    continue;
+                }
+
+                let swapped = a.position == undo.players[j].position && b.position == undo.players[i].position;
+                if a.position != b.position && !swapped {
+                    continue;
+                }
+
+                match (a.is_dangerous(), b.is_dangerous()) {
+                    (true, false) => events.push(self.eat(i, j)),
+                    (false, true) => events.push(self.eat(j, i)),
+                    _ => {},
+                }
+            }
+        }
+
+        self.history.push(undo);
+        events
+    }
+
+    fn eat(&mut self, eater: usize, eaten: usize) -> Event {
+        self.players[eater].score += self.scores.eat_player;
+        self.players[eaten].alive = false;
+        Event::Kill { eater, eaten }
+    }
+
+    // Takes back the last tick that was applied, returns false if there was nothing to take back
+    pub fn undo(&mut self) -> bool {
+        let undo = match self.history.pop() {
+            Some(u) => u,
+            None => return false,
+        };
+
+        for index in undo.eaten_pellets {
+            self.pellets[index / 64] |= 1 << (index % 64);
+        }
+
+        self.players = undo.players;
+        self.tick -= 1;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use game::TileType;
+    use protocol::Message;
+    use std::collections::HashSet;
+    use std::fs;
+    use std::str::FromStr;
+
+    const STATE: &str = r#"{"gamestate":{"map":{"content":["|||||||","|.o___|","|_|||_|","|_____|","|||||||"],"height":5,"pelletsleft":2,"width":7},"others":[{"id":1,"isdangerous":false,"score":0,"x":4,"y":1}],"you":{"id":0,"isdangerous":false,"score":10,"x":1,"y":3}},"messagetype":"stateupdate"}"#;
+
+    // Consecutive state updates written by hand from how we understand the rules
+    const RULES_FIXTURE: &str = include_str!("corpus/rules_fixture.jsonl");
+
+    // Games recorded from the server with --record, one message per line
+    const RECORDED: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src/game/corpus/recorded");
+
+    fn parse(message: &str) -> GameState {
+        match Message::from_str(message).unwrap() {
+            Message::Update { state } => state,
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn can_eat_pellets_and_players() {
+        let state = parse(STATE);
+        let mut sim = SimState::from_game_state(&state, 0);
+        assert_eq!(2, sim.pellets().count());
+        assert_eq!(vec![Direction::Up, Direction::Right], sim.legal_moves(0));
+
+        assert!(sim.apply(&[Direction::Up, Direction::Left]).is_empty());
+        let events = sim.apply(&[Direction::Up, Direction::Left]);
+        assert_eq!(vec![Event::Pellet { player: 0 }, Event::SuperPellet { player: 1 }], events);
+        assert_eq!(11, sim.me().score);
+        assert_eq!(0, sim.pellets().count());
+
+        // The enemy walks through us
+        let events = sim.apply(&[Direction::Right, Direction::Left]);
+        assert_eq!(vec![Event::Kill { eater: 1, eaten: 0 }], events);
+        assert!(!sim.me().alive);
+        assert_eq!(rules::ESTIMATED_SCORES.super_pellet + rules::ESTIMATED_SCORES.eat_player, sim.players[1].score);
+    }
+
+    #[test]
+    fn should_stand_still_when_walking_in_to_walls() {
+        let state = parse(STATE);
+        let mut sim = SimState::from_game_state(&state, 0);
+
+        sim.apply(&[Direction::Down, Direction::Up]);
+        assert_eq!(Position::new(1, 3), sim.me().position);
+        assert_eq!(Position::new(4, 1), sim.players[1].position);
+    }
+
+    #[test]
+    fn can_undo_back_to_the_start() {
+        let state = parse(STATE);
+        let mut sim = SimState::from_game_state(&state, 0);
+        let start_players = sim.players.clone();
+        let start_pellets: Vec<Position> = sim.pellets().collect();
+
+        for _ in 0..3 {
+            sim.apply(&[Direction::Up, Direction::Left]);
+        }
+        sim.apply(&[Direction::Right, Direction::Left]);
+
+        for _ in 0..4 {
+            assert!(sim.undo());
+        }
+        assert!(!sim.undo());
+
+        assert_eq!(start_players, sim.players);
+        assert_eq!(start_pellets, sim.pellets().collect::<Vec<Position>>());
+        assert_eq!(2, sim.pellets().count());
+        assert_eq!(0, sim.tick);
+    }
+
+    // The move that takes a player from one position to the next, None if it didn't get there by walking
+    fn infer_move(map: &Map, from: &Position, to: &Position) -> Option<Direction> {
+        if from == to {
+            return DIRECTIONS
+                .iter()
+                .find(|d| !map.tile_at(&from.adjacent(map, d)).is_walkable())
+                .cloned();
+        }
+
+        DIRECTIONS.iter().find(|d| from.adjacent(map, d) == *to).cloned()
+    }
+
+    // Replays every pair of consecutive state updates through the simulator and returns how many there were.
+    // Anything else the server sends (deaths, new rounds) breaks the game up in to separate runs.
+    fn check_transitions(game: &str) -> usize {
+        let mut runs: Vec<Vec<GameState>> = vec![Vec::new()];
+        for line in game.lines().filter(|l| !l.trim().is_empty()) {
+            match Message::from_str(line).unwrap() {
+                Message::Update { state } => runs.last_mut().unwrap().push(state),
+                _ => runs.push(Vec::new()),
+            }
+        }
+
+        let mut transitions = 0;
+        for states in runs {
+            for pair in states.windows(2) {
+                let (before, after) = (&pair[0], &pair[1]);
+                let mut sim = SimState::from_game_state(before, if before.me.is_dangerous { rules::TICKS_DANGEROUS } else { 0 });
+
+                let actual: Vec<(Position, u32, bool)> = sim.players
+                    .iter()
+                    .map(|p| {
+                        let player = if p.id == after.me.id { &after.me } else { after.enemies.iter().find(|e| e.id == p.id).unwrap() };
+                        (player.position(), player.score, player.is_dangerous)
+                    })
+                    .collect();
+
+                let moves: Vec<Option<Direction>> = sim.players
+                    .iter()
+                    .zip(&actual)
+                    .map(|(p, a)| infer_move(&before.map, &p.position, &a.0))
+                    .collect();
+                let events = sim.apply(&moves.iter().map(|m| m.clone().unwrap_or(Direction::Up)).collect::<Vec<Direction>>());
+
+                // The server doesn't send events, but pellets are gone from under whoever ate them and players that
+                // were eaten don't get where they were going. Who ate them can't be told.
+                let mut expected_events = Vec::new();
+                for (i, (a, walked)) in actual.iter().zip(&moves).enumerate() {
+                    let eaten = before.map.tile_at(&a.0).is_pellet() && !after.map.tile_at(&a.0).is_pellet();
+                    match (walked, before.map.tile_at(&a.0)) {
+                        (&None, _) => expected_events.push(Event::Kill { eater: usize::MAX, eaten: i }),
+                        (_, TileType::SuperPellet) if eaten => expected_events.push(Event::SuperPellet { player: i }),
+                        (_, TileType::Pellet) if eaten => expected_events.push(Event::Pellet { player: i }),
+                        _ => {},
+                    }
+                }
+                let mut simulated_events: Vec<Event> = events
+                    .into_iter()
+                    .map(|e| match e {
+                        Event::Kill { eaten, .. } => Event::Kill { eater: usize::MAX, eaten },
+                        e => e,
+                    })
+                    .collect();
+                let key = |e: &Event| format!("{:?}", e);
+                expected_events.sort_by_key(&key);
+                simulated_events.sort_by_key(&key);
+                simulated_events.dedup();
+                assert_eq!(expected_events, simulated_events, "tick {}: events", transitions);
+
+                for ((player, expected), walked) in sim.players.iter().zip(&actual).zip(&moves) {
+                    // Players that were eaten respawn somewhere else
+                    if !player.alive || walked.is_none() {
+                        assert!(!player.alive && walked.is_none(), "tick {}: player {} was eaten in only one of them", transitions, player.id);
+                        continue;
+                    }
+
+                    assert_eq!(*expected, (player.position.clone(), player.score, player.is_dangerous()), "tick {}: player {}", transitions, player.id);
+                }
+
+                let expected_pellets: HashSet<Position> = after.map.pellets();
+                assert_eq!(expected_pellets, sim.pellets().collect::<HashSet<Position>>(), "tick {}", transitions);
+                transitions += 1;
+            }
+        }
+
+        transitions
+    }
+
+    #[test]
+    fn should_agree_with_rules_fixture() {
+        assert!(check_transitions(RULES_FIXTURE) > 0);
+    }
+
+    #[test]
+    #[ignore = "needs games recorded from the server with --record"]
+    fn should_agree_with_recorded_games() {
+        let mut transitions = 0;
+        for entry in fs::read_dir(RECORDED).unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().is_some_and(|e| e == "jsonl") {
+                transitions += check_transitions(&fs::read_to_string(&path).unwrap());
+            }
+        }
+
+        assert!(transitions > 0, "no recorded games in {}", RECORDED);
+    }
+}
@@ -0,0 +1,151 @@
+use log;
+use log::{Log, LogLevelFilter, LogMetadata, LogRecord};
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::io::{LineWriter, Write};
+use std::str::FromStr;
+use std::sync::Mutex;
+
+// Where log lines end up, anything but stdout or stderr is taken to be a file
+#[derive(Debug, PartialEq)]
+pub enum Target {
+    Stdout,
+    Stderr,
+    File(String),
+}
+
+impl<'a> From<&'a str> for Target {
+    fn from(target: &'a str) -> Target {
+        match target {
+            "stdout" => Target::Stdout,
+            "stderr" => Target::Stderr,
+            path => Target::File(path.to_string()),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    InvalidFilter(String),
+    AlreadyInitialized,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Io(ref e) => write!(f, "can't open the log file, {}", e),
+            Error::InvalidFilter(ref spec) => write!(f, "invalid log levels {}", spec),
+            Error::AlreadyInitialized => write!(f, "logging is already set up"),
+        }
+    }
+}
+
+// Levels per module, like "warn,allie::ai::safety=debug". The most specific module wins, modules that aren't
+// mentioned get the level without a module.
+#[derive(Debug, PartialEq)]
+pub struct Filter {
+    default: LogLevelFilter,
+    modules: Vec<(String, LogLevelFilter)>,
+}
+
+impl Filter {
+    pub fn parse(spec: &str) -> Result<Filter, Error> {
+        let invalid = || Error::InvalidFilter(spec.to_string());
+        let mut filter = Filter {
+            default: LogLevelFilter::Off,
+            modules: Vec::new(),
+        };
+
+        for directive in spec.split(',').map(|d| d.trim()).filter(|d| !d.is_empty()) {
+            let mut parts = directive.splitn(2, '=');
+            match (parts.next(), parts.next()) {
+                (Some(level), None) => filter.default = LogLevelFilter::from_str(level).map_err(|_| invalid())?,
+                (Some(module), Some(level)) if !module.is_empty() => {
+                    let level = LogLevelFilter::from_str(level).map_err(|_| invalid())?;
+                    filter.modules.push((module.to_string(), level));
+                },
+                _ => return Err(invalid()),
+            }
+        }
+
+        Ok(filter)
+    }
+
+    fn level_for(&self, target: &str) -> LogLevelFilter {
+        self.modules
+            .iter()
+            .filter(|(module, _)| target == module || target.starts_with(&format!("{}::", module)))
+            .max_by_key(|(module, _)| module.len())
+            .map(|&(_, level)| level)
+            .unwrap_or(self.default)
+    }
+
+    fn max(&self) -> LogLevelFilter {
+        self.modules.iter().map(|&(_, level)| level).fold(self.default, |a, b| a.max(b))
+    }
+}
+
+struct Logger {
+    filter: Filter,
+    out: Mutex<Box<dyn Write + Send>>,
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &LogMetadata) -> bool {
+        metadata.level() <= self.filter.level_for(metadata.target())
+    }
+
+    fn log(&self, record: &LogRecord) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        // Losing a log line is not worth crashing over
+        if let Ok(mut out) = self.out.lock() {
+            let _ = writeln!(out, "{:<5} [{}] {}", record.level(), record.target(), record.args());
+        }
+    }
+}
+
+pub fn init(filter: Filter, target: Target) -> Result<(), Error> {
+    let out: Box<dyn Write + Send> = match target {
+        Target::Stdout => Box::new(io::stdout()),
+        Target::Stderr => Box::new(io::stderr()),
+        Target::File(path) => Box::new(LineWriter::new(File::create(path).map_err(Error::Io)?)),
+    };
+
+    let max = filter.max();
+    log::set_logger(|max_log_level| {
+        max_log_level.set(max);
+        Box::new(Logger {
+            filter,
+            out: Mutex::new(out),
+        })
+    })
+    .map_err(|_| Error::AlreadyInitialized)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_filter_per_module() {
+        let filter = Filter::parse("warn, allie::ai=info,allie::ai::safety=trace").unwrap();
+        assert_eq!(LogLevelFilter::Warn, filter.level_for("allie::client"));
+        assert_eq!(LogLevelFilter::Info, filter.level_for("allie::ai"));
+        assert_eq!(LogLevelFilter::Info, filter.level_for("allie::ai::strategies::killer"));
+        assert_eq!(LogLevelFilter::Trace, filter.level_for("allie::ai::safety"));
+        assert_eq!(LogLevelFilter::Info, filter.level_for("allie::ai::safety_net"));
+        assert_eq!(LogLevelFilter::Trace, filter.max());
+
+        assert_eq!(LogLevelFilter::Off, Filter::parse("").unwrap().level_for("allie"));
+        assert!(Filter::parse("loud").is_err());
+        assert!(Filter::parse("=debug").is_err());
+
+        assert_eq!(Target::Stderr, Target::from("stderr"));
+        assert_eq!(Target::File("allie.log".to_string()), Target::from("allie.log"));
+    }
+}
@@ -3,13 +3,18 @@
 #[macro_use]
 extern crate clap;
 extern crate itertools;
+#[macro_use]
+extern crate log;
 extern crate pathfinding;
+extern crate rand;
 extern crate serde;
 #[macro_use]
 extern crate serde_derive;
 extern crate serde_json;
 
 use clap::{App, Arg};
+use std::fs::File;
+use std::io::{LineWriter, Write};
 use std::net::SocketAddrV4;
 use std::str::FromStr;
 use std::time::{Duration, Instant};
@@ -18,14 +23,25 @@ mod ai;
 mod client;
 mod common;
 mod game;
+mod logging;
 mod protocol;
 mod traits;
 
+use ai::config::BotConfig;
 use client::AIClient;
 use protocol::Message;
 
 const ARG_IP: &'static str = "ip";
 const ARG_PORT: &'static str = "port";
+const ARG_CONFIG: &str = "config";
+const ARG_ENABLE: &str = "enable";
+const ARG_DISABLE: &str = "disable";
+const ARG_PRIORITY: &str = "priority";
+const ARG_PARAM: &str = "param";
+const ARG_TRACE: &str = "trace";
+const ARG_RECORD: &str = "record";
+const ARG_LOG: &str = "log";
+const ARG_LOG_TARGET: &str = "log-target";
 
 fn main() {
     let arguments = App::new("Allie")
@@ -42,8 +58,93 @@ fn main() {
             .value_name("PORT")
             .help("TCP port\t(default 54321)")
             .takes_value(true))
+        .arg(Arg::with_name(ARG_CONFIG)
+            .long("config")
+            .value_name("FILE")
+            .help("JSON file with the strategies to use and their parameters")
+            .takes_value(true))
+        .arg(Arg::with_name(ARG_ENABLE)
+            .long("enable")
+            .value_name("STRATEGY")
+            .help("Enables a strategy, can be given multiple times")
+            .takes_value(true)
+            .multiple(true)
+            .number_of_values(1))
+        .arg(Arg::with_name(ARG_DISABLE)
+            .long("disable")
+            .value_name("STRATEGY")
+            .help("Disables a strategy, can be given multiple times")
+            .takes_value(true)
+            .multiple(true)
+            .number_of_values(1))
+        .arg(Arg::with_name(ARG_PRIORITY)
+            .long("priority")
+            .value_name("STRATEGIES")
+            .help("Comma separated strategies to move to the top of the priority order")
+            .takes_value(true))
+        .arg(Arg::with_name(ARG_PARAM)
+            .long("param")
+            .value_name("STRATEGY.NAME=VALUE")
+            .help("Sets a strategy parameter, can be given multiple times")
+            .takes_value(true)
+            .multiple(true)
+            .number_of_values(1))
+        .arg(Arg::with_name(ARG_RECORD)
+            .long("record")
+            .value_name("FILE")
+            .help("Writes every message from the server to a file, as a recorded game for the simulator corpus")
+            .takes_value(true))
+        .arg(Arg::with_name(ARG_TRACE)
+            .long("trace")
+            .value_name("FILE")
+            .help("Writes what went in to every decision to a file, one JSON record per tick")
+            .takes_value(true))
+        .arg(Arg::with_name(ARG_LOG)
+            .long("log")
+            .value_name("LEVELS")
+            .help("Log levels, per module if needed, like \"info,allie::ai::safety=debug\"\t(default debug, or warn for release builds)")
+            .takes_value(true))
+        .arg(Arg::with_name(ARG_LOG_TARGET)
+            .long("log-target")
+            .value_name("TARGET")
+            .help("Where to log to, stdout, stderr or a file\t(default stdout)")
+            .takes_value(true))
         .get_matches();
 
+    // Release builds are what we compete with, those should keep quiet
+    let levels = arguments.value_of(ARG_LOG).unwrap_or(if cfg!(debug_assertions) { "debug" } else { "warn" });
+    let target = logging::Target::from(arguments.value_of(ARG_LOG_TARGET).unwrap_or("stdout"));
+    if let Err(e) = logging::Filter::parse(levels).and_then(|filter| logging::init(filter, target)) {
+        println!("Unable to set up logging, {}", e);
+        std::process::exit(1);
+    }
+
+    let config = match load_config(&arguments) {
+        Ok(config) => config,
+        Err(e) => {
+            error!("Invalid configuration, {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let mut recording = match arguments.value_of(ARG_RECORD).map(File::create) {
+        Some(Err(e)) => {
+            error!("Unable to create recording ({})", e);
+            std::process::exit(1);
+        },
+        Some(Ok(file)) => Some(LineWriter::new(file)),
+        None => None,
+    };
+
+    let mut tracer = match arguments.value_of(ARG_TRACE).map(ai::trace::Tracer::create) {
+        Some(Err(e)) => {
+            error!("Unable to create trace file ({})", e);
+            std::process::exit(1);
+        },
+        Some(Ok(tracer)) => Some(tracer),
+        None => None,
+    };
+
     let host = {
         let ip = arguments.value_of(ARG_IP).unwrap_or("127.0.0.1");
         let port = arguments.value_of(ARG_PORT).unwrap_or("54321");
@@ -51,13 +152,13 @@ fn main() {
     };
 
     if host.is_err() {
-        println!("Invalid IP address or port ({})", host.err().unwrap());
+        error!("Invalid IP address or port ({})", host.err().unwrap());
         std::process::exit(1);
     }
 
     let client = client::tcp::connect(host.unwrap());
     if client.is_none() {
-        println!("Failed to connect to server");
+        error!("Failed to connect to server");
         std::process::exit(1);
     }
 
@@ -74,16 +175,27 @@ fn main() {
     let mut bot: Option<ai::Bot> = None;
 
     while client.wait_response() {
+        if let Some(ref mut file) = recording {
+            if let Err(e) = file.write_all(client.raw_response().as_bytes()) {
+                error!("Failed to write recording, no longer recording ({})", e);
+                recording = None;
+            }
+        }
+
         let response = client.response();
 
         if response.is_err() {
-            println!("Response error: {:?}", response.err().unwrap());
+            warn!("Response error: {:?}", response.err().unwrap());
             continue;
         }
 
         match response.unwrap() {
             Message::Welcome { state } => {
-                bot = Some(ai::Bot::from_game_state(&state));
+                let mut new_bot = ai::Bot::with_config(&state, &config);
+                if let Some(tracer) = tracer.take() {
+                    new_bot.set_tracer(tracer);
+                }
+                bot = Some(new_bot);
             }
             Message::StartOfRound => {
                 if let Some(ref mut x) = bot {
@@ -93,21 +205,61 @@ fn main() {
             Message::Update { state } => {
                 match bot {
                     Some(ref mut x) => {
-                        // let instant = Instant::now();
+                        let instant = Instant::now();
                         let action = x.determine_action(state);
-                        // println!("Time to determine action: {:>10.3} ms", duration_in_ms(&instant.elapsed()));
+                        debug!("Time to determine action: {:>10.3} ms", duration_in_ms(&instant.elapsed()));
                         client.send_action(&action);
                     },
                     None => debug_assert!(false, "Received stateupdate message while not having an initialized AI"),
                 }
             }
-            Message::Dead | Message::EndOfRound => {
-                // Nothing special to do here
+            Message::Dead => {
+                // We might respawn later this round, or not until the next one
+                if let Some(ref mut x) = bot {
+                    x.on_death();
+                }
+            }
+            Message::EndOfRound => {
+                if let Some(ref x) = bot {
+                    let blame: Vec<String> = ai::death::blame(x.deaths())
+                        .into_iter()
+                        .map(|(strategy, count)| match strategy {
+                            Some(s) => format!("{} while {:?} was in control", count, s),
+                            None => format!("{} before any strategy took control", count),
+                        })
+                        .collect();
+                    info!("Died {} times this round ({})", x.deaths().len(), blame.join(", "));
+                }
             }
         }
     }
 }
 
+fn load_config(arguments: &clap::ArgMatches) -> Result<BotConfig, ai::config::Error> {
+    let mut config = match arguments.value_of(ARG_CONFIG) {
+        Some(path) => BotConfig::from_file(path)?,
+        None => BotConfig::default(),
+    };
+
+    for name in arguments.values_of(ARG_ENABLE).into_iter().flatten() {
+        config.set_enabled(name, true)?;
+    }
+
+    for name in arguments.values_of(ARG_DISABLE).into_iter().flatten() {
+        config.set_enabled(name, false)?;
+    }
+
+    if let Some(priority) = arguments.value_of(ARG_PRIORITY) {
+        config.prioritize(&priority.split(',').map(|x| x.trim()).collect::<Vec<&str>>())?;
+    }
+
+    for assignment in arguments.values_of(ARG_PARAM).into_iter().flatten() {
+        config.set_param(assignment)?;
+    }
+
+    Ok(config)
+}
+
 fn duration_in_ms(duration: &Duration) -> f32 {
     (duration.as_secs() as f32 * 1000.0) + (duration.subsec_nanos() as f32 / 1_000_000.0)
 }
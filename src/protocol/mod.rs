@@ -21,13 +21,13 @@ pub struct GameState {
 
 #[derive(Debug, Deserialize)]
 pub struct Player {
-    id: u32,
+    pub id: u32,
     x: u32,
     y: u32,
 
     // These fields are not present in the welcome message, default them in that case
     #[serde(default)]
-    score: u32,
+    pub score: u32,
     #[serde(default, rename = "isdangerous")]
     pub is_dangerous: bool,
 }